@@ -0,0 +1,20 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use streamdeck::{Colour, Kind};
+
+fn colour_image(c: &mut Criterion) {
+    let colour = Colour {
+        r: 0xff,
+        g: 0x80,
+        b: 0x00,
+    };
+
+    for kind in [Kind::Mini, Kind::Xl] {
+        c.bench_function(&format!("colour_image {:?}", kind), |b| {
+            b.iter(|| kind.colour_image(black_box(&colour)))
+        });
+    }
+}
+
+criterion_group!(benches, colour_image);
+criterion_main!(benches);
@@ -0,0 +1,76 @@
+//! Exercise the streamdeck API against a [MockDevice], without hardware
+//!
+//! Run with `cargo run --example mock [FONT]`, where `FONT` is an optional
+//! TrueType / OpenType font file for rendering button text.
+
+use ab_glyph::FontRef;
+
+use streamdeck::{
+    Colour, Error, InputManager, Kind, MockDevice, StreamDeck, TextOptions, TextPosition,
+};
+
+const DEFAULT_FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+fn main() -> Result<(), Error> {
+    let font_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_FONT.to_string());
+
+    // Create a device using the mock transport, retaining a handle
+    // to inspect written reports and queue input
+    let mock = MockDevice::new();
+    let mut deck = StreamDeck::with_transport(Kind::Plus, Box::new(mock.clone()));
+
+    // Set a button colour
+    let red = Colour { r: 255, g: 0, b: 0 };
+    deck.set_button_rgb(0, &red)?;
+    print_writes("set_button_rgb", &mock);
+
+    // Set button text, where a font is available
+    match std::fs::read(&font_path) {
+        Ok(data) => {
+            let font = FontRef::try_from_slice(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+            let opts = TextOptions::default();
+            deck.set_button_text(1, &font, &TextPosition::centered(), "Hello", &opts)?;
+            print_writes("set_button_text", &mock);
+        }
+        Err(e) => println!("Skipping text, failed to load font '{}': {}", font_path, e),
+    }
+
+    // Queue input reports as they would be received from a Stream Deck Plus
+    let mut keys = vec![0x01, 0x00, 0x08, 0x00];
+    keys.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    mock.push_input(&keys);
+
+    // Dial 1 turned two steps clockwise
+    mock.push_input(&[0x01, 0x03, 0x05, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00]);
+
+    // Short touch at (300, 50)
+    let mut touch = vec![0x01, 0x02, 0x0e, 0x00, 0x01, 0x00];
+    touch.extend_from_slice(&[0x2c, 0x01, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    mock.push_input(&touch);
+
+    // Decode input events, ending when no more reports are queued
+    let mut manager = InputManager::new(&mut deck);
+    let mut events = manager.events(None);
+    for e in &mut events {
+        println!("input: {}", e);
+    }
+    if let Some(e) = events.take_error() {
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Print and clear the reports written to the mock device
+fn print_writes(name: &str, mock: &MockDevice) {
+    let writes = mock.writes();
+    let bytes: usize = writes.iter().map(|w| w.len()).sum();
+
+    println!("{}: {} reports ({} bytes)", name, writes.len(), bytes);
+
+    mock.clear();
+}
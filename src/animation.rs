@@ -0,0 +1,447 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ab_glyph::FontRef;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, Rgb, RgbImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+
+use crate::{DeviceImage, Error, ImageOptions, StreamDeck, TextOptions};
+
+/// Frame sequence player for pre-converted device images
+///
+/// Each frame is displayed for the associated duration, by default
+/// the sequence is played once.
+pub struct Animation {
+    frames: Vec<(DeviceImage, Duration)>,
+    index: usize,
+    next: Option<Instant>,
+    loop_count: Option<u32>,
+    loops: u32,
+}
+
+impl Animation {
+    /// Create an animation from a sequence of frames and durations
+    pub fn new(frames: Vec<(DeviceImage, Duration)>) -> Self {
+        Self {
+            frames,
+            index: 0,
+            next: None,
+            loop_count: Some(1),
+            loops: 0,
+        }
+    }
+
+    /// Load an animation from a directory of numbered images (ie. `frame-01.png`, `frame-02.png`),
+    /// displaying each frame for the provided delay
+    pub fn from_dir<P: AsRef<Path>>(
+        deck: &StreamDeck,
+        dir: P,
+        delay: Duration,
+        opts: &ImageOptions,
+    ) -> Result<Self, Error> {
+        let mut paths = vec![];
+        for e in std::fs::read_dir(dir)? {
+            let p = e?.path();
+            if p.is_file() {
+                paths.push(p);
+            }
+        }
+
+        // Order by frame number, falling back to the file name
+        paths.sort_by_key(|p| (frame_number(p), p.clone()));
+
+        let mut frames = vec![];
+        for p in paths {
+            frames.push((deck.load_image(&p, opts)?, delay));
+        }
+
+        if frames.is_empty() {
+            return Err(Error::NoData);
+        }
+
+        Ok(Self::new(frames))
+    }
+
+    /// Set the number of times the animation should be played,
+    /// `None` loops forever
+    pub fn with_loop_count(mut self, loop_count: Option<u32>) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    /// Check whether the animation has completed the configured number of loops
+    pub fn is_finished(&self) -> bool {
+        match self.loop_count {
+            Some(n) => self.frames.is_empty() || self.loops >= n,
+            None => self.frames.is_empty(),
+        }
+    }
+
+    /// Fetch the next frame if it is due for display, advancing the animation
+    ///
+    /// Returns `None` if the current frame should still be displayed
+    /// or the animation has finished
+    pub fn next_frame(&mut self) -> Option<&DeviceImage> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let now = Instant::now();
+        if let Some(next) = self.next {
+            if now < next {
+                return None;
+            }
+        }
+
+        let index = self.index;
+        self.next = Some(now + self.frames[index].1);
+
+        self.index += 1;
+        if self.index >= self.frames.len() {
+            self.index = 0;
+            self.loops += 1;
+        }
+
+        Some(&self.frames[index].0)
+    }
+
+    /// Write the next frame to the provided key if it is due
+    ///
+    /// This should be called at least as often as the shortest frame duration
+    pub fn tick(&mut self, deck: &mut StreamDeck, key: u8) -> Result<(), Error> {
+        if let Some(image) = self.next_frame() {
+            deck.write_button_image(key, image)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the trailing frame number from a file name
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let start = stem
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    stem[start..].parse().ok()
+}
+
+/// Animated GIF playback on a single key, see [StreamDeck::play_gif]
+///
+/// Unlike [Animation] this loops forever by default
+pub struct GifAnimation {
+    key: u8,
+    animation: Animation,
+}
+
+impl GifAnimation {
+    /// Load and pre-convert the frames of a GIF file for the provided device and key
+    pub fn load<P: AsRef<Path>>(
+        deck: &StreamDeck,
+        key: u8,
+        path: P,
+        opts: &ImageOptions,
+    ) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let decoder = GifDecoder::new(reader)?;
+
+        let mut frames = vec![];
+        for f in decoder.into_frames().collect_frames()? {
+            let delay = Duration::from(f.delay());
+            let image = deck.prepare_image(DynamicImage::ImageRgba8(f.into_buffer()), opts)?;
+            frames.push((image, delay));
+        }
+
+        if frames.is_empty() {
+            return Err(Error::NoData);
+        }
+
+        Ok(Self {
+            key,
+            animation: Animation::new(frames).with_loop_count(None),
+        })
+    }
+
+    /// Set the number of times the animation should be played,
+    /// `None` (the default) loops forever
+    pub fn with_loop_count(mut self, loop_count: Option<u32>) -> Self {
+        self.animation = self.animation.with_loop_count(loop_count);
+        self
+    }
+
+    /// Fetch the key this animation is displayed on
+    pub fn key(&self) -> u8 {
+        self.key
+    }
+
+    /// Check whether the animation has completed the configured number of loops
+    pub fn is_finished(&self) -> bool {
+        self.animation.is_finished()
+    }
+
+    /// Write the next frame to the device if it is due
+    ///
+    /// This should be called at least as often as the shortest frame delay,
+    /// and does nothing once the animation is finished
+    pub fn tick(&mut self, deck: &mut StreamDeck) -> Result<(), Error> {
+        self.animation.tick(deck, self.key)
+    }
+}
+
+/// Horizontally scrolling text for a single key
+///
+/// The text is rendered once to a strip which is then scrolled through
+/// the key viewport on each call to `tick`, wrapping around at the end.
+pub struct Marquee {
+    strip: RgbImage,
+    background: Rgb<u8>,
+    offset: u32,
+    speed: u32,
+}
+
+impl Marquee {
+    /// Render the provided text to a marquee strip, newlines are replaced with spaces
+    pub fn new(font: &FontRef, text: &str, opts: &TextOptions) -> Self {
+        let text = text.replace('\n', " ");
+        let background = Rgb([opts.background.r, opts.background.g, opts.background.b]);
+        let colour = Rgb([opts.foreground.r, opts.foreground.g, opts.foreground.b]);
+
+        // Pad the strip with a gap so the wrap-around is readable
+        let (w, h) = text_size(opts.scale, font, &text);
+        let gap = opts.scale.x.round() as u32 * 2;
+        let height = h.max((opts.scale.y * opts.line_height).round() as u32);
+
+        let mut strip = ImageBuffer::from_pixel((w + gap).max(1), height.max(1), background);
+        draw_text_mut(&mut strip, colour, 0, 0, opts.scale, font, &text);
+
+        Self {
+            strip,
+            background,
+            offset: 0,
+            speed: 1,
+        }
+    }
+
+    /// Set the scrolling speed in pixels per tick (defaults to 1)
+    pub fn with_speed(mut self, speed: u32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Write the current viewport to the provided key and advance the marquee
+    pub fn tick(&mut self, deck: &mut StreamDeck, key: u8) -> Result<(), Error> {
+        let (width, height) = deck.image_size();
+        let (width, height) = (width as u32, height as u32);
+        let (strip_width, strip_height) = self.strip.dimensions();
+
+        // Vertically center the strip in the key
+        let y_offset = height.saturating_sub(strip_height) / 2;
+
+        let mut image = ImageBuffer::from_pixel(width, height, self.background);
+        for x in 0..width {
+            let sx = (self.offset + x) % strip_width;
+            for y in 0..strip_height.min(height) {
+                image.put_pixel(x, y_offset + y, *self.strip.get_pixel(sx, y));
+            }
+        }
+
+        self.offset = (self.offset + self.speed) % strip_width;
+
+        deck.set_button_image(key, DynamicImage::ImageRgb8(image))
+    }
+}
+
+/// Destination for frames written to a [FrameSink]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameTarget {
+    /// Write each frame to a single key
+    Key(u8),
+    /// Split each frame across all keys, see [StreamDeck::set_mosaic]
+    Mosaic,
+}
+
+/// Streaming frame writer for video-like content
+///
+/// Frames are prepared and written as they are provided, paced to the target
+/// frame rate. Writing enables de-duplication on the device (see
+/// [StreamDeck::set_dedupe]) so keys are only re-written when their image changes,
+/// and where frame dropping is enabled frames arriving more than one frame interval
+/// late are skipped to catch up with the stream.
+pub struct FrameSink {
+    target: FrameTarget,
+    interval: Duration,
+    drop_frames: bool,
+    start: Option<Instant>,
+    next: Option<Instant>,
+    written: u64,
+    dropped: u64,
+}
+
+impl FrameSink {
+    /// Create a frame sink for the provided target at the target frame rate,
+    /// returning `Error::InvalidFrameRate` unless this is positive and finite
+    pub fn new(target: FrameTarget, fps: f32) -> Result<Self, Error> {
+        // Also rejects NaN, which fails all comparisons
+        if !(fps > 0.0 && fps.is_finite()) {
+            return Err(Error::InvalidFrameRate(fps));
+        }
+
+        Ok(Self {
+            target,
+            interval: Duration::from_secs_f32(1.0 / fps),
+            drop_frames: false,
+            start: None,
+            next: None,
+            written: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Set whether late frames are dropped (defaults to false)
+    pub fn with_drop_frames(mut self, drop_frames: bool) -> Self {
+        self.drop_frames = drop_frames;
+        self
+    }
+
+    /// Write a frame to the device, blocking until the frame is due
+    ///
+    /// Returns `false` if the frame was dropped
+    pub fn write(
+        &mut self,
+        deck: &mut StreamDeck,
+        image: DynamicImage,
+        opts: &ImageOptions,
+    ) -> Result<bool, Error> {
+        self.write_at(deck, image, opts, Instant::now())
+    }
+
+    /// Write a frame arriving at the provided time, see `write`
+    fn write_at(
+        &mut self,
+        deck: &mut StreamDeck,
+        image: DynamicImage,
+        opts: &ImageOptions,
+        now: Instant,
+    ) -> Result<bool, Error> {
+        let due = self.next.unwrap_or(now);
+
+        if now < due {
+            std::thread::sleep(due - now);
+        } else if self.drop_frames && now >= due + self.interval {
+            self.next = Some(due + self.interval);
+            self.dropped += 1;
+            return Ok(false);
+        }
+
+        let images = match self.target {
+            FrameTarget::Key(key) => vec![(key, deck.prepare_image(image, opts)?)],
+            FrameTarget::Mosaic => deck.prepare_mosaic(image, opts)?,
+        };
+
+        // Keys unchanged from the last frame are skipped by the device,
+        // enabling this clears the existing hashes so is only set once
+        if !deck.is_dedupe() {
+            deck.set_dedupe(true);
+        }
+        for (key, image) in images {
+            deck.write_button_image(key, &image)?;
+        }
+
+        self.start.get_or_insert(due);
+        self.next = Some(due + self.interval);
+        self.written += 1;
+
+        Ok(true)
+    }
+
+    /// Write a sequence of frames, returning the achieved frame rate
+    pub fn play<I: IntoIterator<Item = DynamicImage>>(
+        &mut self,
+        deck: &mut StreamDeck,
+        frames: I,
+        opts: &ImageOptions,
+    ) -> Result<f32, Error> {
+        for f in frames {
+            self.write(deck, f, opts)?;
+        }
+        Ok(self.fps())
+    }
+
+    /// Achieved frame rate over the frames written so far
+    pub fn fps(&self) -> f32 {
+        let elapsed = match self.start {
+            Some(s) => s.elapsed().as_secs_f32(),
+            None => return 0.0,
+        };
+        match elapsed > 0.0 {
+            true => self.written as f32 / elapsed,
+            false => 0.0,
+        }
+    }
+
+    /// Number of frames written
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Number of frames dropped
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::mock_deck;
+    use crate::Kind;
+
+    #[test]
+    fn frame_sink() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+        let opts = ImageOptions::default();
+        let frame = |v| DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([v; 3])));
+
+        for fps in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                FrameSink::new(FrameTarget::Mosaic, fps),
+                Err(Error::InvalidFrameRate(_))
+            ));
+        }
+
+        let mut sink = FrameSink::new(FrameTarget::Mosaic, 1000.0).unwrap();
+        assert!(sink.write(&mut deck, frame(255), &opts).unwrap());
+        let writes = mock.writes().len();
+        assert!(writes > 0);
+        assert!(deck.is_dedupe());
+
+        // Unchanged frames are not re-written
+        mock.clear();
+        assert!(sink.write(&mut deck, frame(255), &opts).unwrap());
+        assert!(mock.writes().is_empty());
+
+        sink.play(&mut deck, vec![frame(0)], &opts).unwrap();
+        assert_eq!(mock.writes().len(), writes);
+        assert_eq!(sink.written(), 3);
+        assert_eq!(sink.dropped(), 0);
+
+        // Frames more than one interval late are dropped
+        let sink = FrameSink::new(FrameTarget::Key(0), 1000.0).unwrap();
+        let mut sink = sink.with_drop_frames(true);
+        let start = Instant::now();
+        assert!(sink.write_at(&mut deck, frame(0), &opts, start).unwrap());
+        let late = start + Duration::from_millis(10);
+        assert!(!sink.write_at(&mut deck, frame(0), &opts, late).unwrap());
+        assert_eq!((sink.written(), sink.dropped()), (1, 1));
+    }
+
+    #[test]
+    fn frame_numbers() {
+        assert_eq!(frame_number(Path::new("frame-01.png")), Some(1));
+        assert_eq!(frame_number(Path::new("dir/12.png")), Some(12));
+        assert_eq!(frame_number(Path::new("frame.png")), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use ab_glyph::FontRef;
+use image::DynamicImage;
+
+use crate::{Colour, DeviceImage, Error, ImageOptions, StreamDeck, TextOptions, TextPosition};
+
+/// Handle to a single button on a [StreamDeck], see [StreamDeck::button]
+///
+/// This forwards operations to the device with the key index baked in,
+/// the index is validated when the handle is created.
+pub struct Button<'a> {
+    deck: &'a mut StreamDeck,
+    key: u8,
+}
+
+impl<'a> Button<'a> {
+    pub(crate) fn new(deck: &'a mut StreamDeck, key: u8) -> Result<Self, Error> {
+        if !deck.is_valid_key(key) {
+            return Err(Error::InvalidKeyIndex);
+        }
+        Ok(Self { deck, key })
+    }
+
+    /// Fetch the key index for this button
+    pub fn index(&self) -> u8 {
+        self.key
+    }
+
+    /// Set the button to the provided RGB colour
+    pub fn set_colour(&mut self, colour: &Colour) -> Result<(), Error> {
+        self.deck.set_button_rgb(self.key, colour)
+    }
+
+    /// Set the button to the provided image
+    pub fn set_image(&mut self, image: DynamicImage) -> Result<(), Error> {
+        self.deck.set_button_image(self.key, image)
+    }
+
+    /// Set the button to an image loaded from a file
+    pub fn set_file<P: AsRef<Path>>(&mut self, path: P, opts: &ImageOptions) -> Result<(), Error> {
+        self.deck.set_button_file(self.key, path, opts)
+    }
+
+    /// Set the button to the provided text, see [StreamDeck::set_button_text]
+    pub fn set_text(
+        &mut self,
+        font: &FontRef,
+        pos: &TextPosition,
+        text: &str,
+        opts: &TextOptions,
+    ) -> Result<(), Error> {
+        self.deck.set_button_text(self.key, font, pos, text, opts)
+    }
+
+    /// Write a prepared image to the button, see [StreamDeck::write_button_image]
+    pub fn write_image(&mut self, image: &DeviceImage) -> Result<(), Error> {
+        self.deck.write_button_image(self.key, image)?;
+        Ok(())
+    }
+
+    /// Clear the button (set to black)
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.set_colour(&Colour { r: 0, g: 0, b: 0 })
+    }
+}
+
+/// Pair of prepared images for on / off buttons
+///
+/// Images are prepared once (see [StreamDeck::prepare_image]) so toggling
+/// only writes the existing device image.
+#[derive(Clone)]
+pub struct ToggleButton {
+    on: DeviceImage,
+    off: DeviceImage,
+    state: bool,
+}
+
+impl ToggleButton {
+    /// Create a toggle button from prepared on and off images, initially off
+    pub fn new(on: DeviceImage, off: DeviceImage) -> Self {
+        Self {
+            on,
+            off,
+            state: false,
+        }
+    }
+
+    /// Fetch the current state
+    pub fn state(&self) -> bool {
+        self.state
+    }
+
+    /// Set the state, writing the matching image to the provided key
+    pub fn set(&mut self, deck: &mut StreamDeck, key: u8, state: bool) -> Result<(), Error> {
+        let image = match state {
+            true => &self.on,
+            false => &self.off,
+        };
+        deck.write_button_image(key, image)?;
+
+        self.state = state;
+
+        Ok(())
+    }
+
+    /// Invert the state, writing the matching image and returning the new state
+    pub fn toggle(&mut self, deck: &mut StreamDeck, key: u8) -> Result<bool, Error> {
+        self.set(deck, key, !self.state)?;
+        Ok(self.state)
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use hidapi::HidApi;
+
+use crate::{interface_rank, Error, ProbedDevice};
+
+/// Default interval for polling the device list
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Device connection events, emitted by a [DeviceWatcher]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceEvent {
+    /// A new device has been connected
+    Connected(ProbedDevice),
+    /// A device has been disconnected, containing the device serial
+    Disconnected(Option<String>),
+}
+
+/// Watcher for devices being connected or disconnected, see [watch_devices]
+///
+/// Events are received via [DeviceWatcher::events].
+/// The polling thread is stopped when the watcher is dropped.
+pub struct DeviceWatcher {
+    events: Receiver<DeviceEvent>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DeviceWatcher {
+    /// Fetch the receiver for device events
+    pub fn events(&self) -> &Receiver<DeviceEvent> {
+        &self.events
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(h) = self.handle.take() {
+            // Wake the thread from the poll interval so this does not block
+            h.thread().unpark();
+            let _ = h.join();
+        }
+    }
+}
+
+/// Watch for devices being connected or disconnected
+///
+/// hidapi does not provide hotplug callbacks on all platforms, so this spawns
+/// a thread which polls the device list every `interval` and emits events
+/// on changes. Devices present when the watch starts are reported as
+/// connected.
+pub fn watch_devices(interval: Duration) -> Result<DeviceWatcher, Error> {
+    let mut api = HidApi::new()?;
+    let (tx, rx) = channel();
+    let running = Arc::new(AtomicBool::new(true));
+
+    let r = running.clone();
+    let handle = std::thread::spawn(move || {
+        let mut known: Vec<ProbedDevice> = vec![];
+
+        while r.load(Ordering::SeqCst) {
+            if let Err(e) = api.refresh_devices() {
+                warn!("Failed to refresh device list: {:?}", e);
+                std::thread::park_timeout(interval);
+                continue;
+            }
+
+            // Collect current devices, de-duplicating multiple interfaces
+            // (which differ only by path) in favour of the preferred interface.
+            // As with `StreamDeck::probe_devices` devices without serials are not de-duplicated.
+            let mut infos: Vec<_> = api.device_list().collect();
+            infos.sort_by_key(|d| interface_rank(d));
+
+            let mut current: Vec<ProbedDevice> = vec![];
+            for d in infos.into_iter().filter_map(ProbedDevice::from_info) {
+                if !current.iter().any(|c| same_device(c, &d)) {
+                    current.push(d);
+                }
+            }
+
+            let mut events = vec![];
+            for d in known
+                .iter()
+                .filter(|d| !current.iter().any(|c| same_device(c, d)))
+            {
+                events.push(DeviceEvent::Disconnected(d.serial.clone()));
+            }
+            for d in current
+                .iter()
+                .filter(|d| !known.iter().any(|k| same_device(k, d)))
+            {
+                events.push(DeviceEvent::Connected(d.clone()));
+            }
+
+            for e in events {
+                debug!("Device event: {:?}", e);
+
+                // Receiver dropped, stop watching
+                if tx.send(e).is_err() {
+                    return;
+                }
+            }
+
+            known = current;
+
+            std::thread::park_timeout(interval);
+        }
+    });
+
+    Ok(DeviceWatcher {
+        events: rx,
+        running,
+        handle: Some(handle),
+    })
+}
+
+/// Compare devices by PID and serial where this is reported (ignoring the interface path),
+/// devices without serials cannot be distinguished so are compared by path
+fn same_device(a: &ProbedDevice, b: &ProbedDevice) -> bool {
+    match (&a.serial, &b.serial) {
+        (Some(sa), Some(sb)) => a.vid == b.vid && a.pid == b.pid && sa == sb,
+        (None, None) => a.path == b.path,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Kind, ELGATO_VID};
+
+    #[test]
+    fn same_devices() {
+        let dev = |serial: Option<&str>, path: &str| ProbedDevice {
+            kind: Kind::Mini,
+            vid: ELGATO_VID,
+            pid: crate::pids::MINI,
+            serial: serial.map(|s| s.to_string()),
+            release: 0,
+            path: path.to_string(),
+        };
+
+        // Interfaces of the same device differ only by path
+        assert!(same_device(&dev(Some("A1"), "1"), &dev(Some("A1"), "2")));
+        assert!(!same_device(&dev(Some("A1"), "1"), &dev(Some("B2"), "1")));
+
+        // Devices without serials are distinguished by path
+        assert!(same_device(&dev(None, "1"), &dev(None, "1")));
+        assert!(!same_device(&dev(None, "1"), &dev(None, "2")));
+        assert!(!same_device(&dev(None, "1"), &dev(Some("A1"), "1")));
+    }
+}
@@ -1,9 +1,13 @@
+use std::path::Path;
 use std::str::FromStr;
 
+#[cfg(not(feature = "mozjpeg"))]
 use image::codecs::jpeg::JpegEncoder;
-use image::io::Reader;
-use image::{imageops::FilterType, Pixel, Rgba};
-use image::{DynamicImage, ExtendedColorType};
+use image::metadata::Orientation;
+#[cfg(not(feature = "mozjpeg"))]
+use image::ExtendedColorType;
+use image::{imageops::FilterType, Pixel, Rgb, RgbImage, Rgba};
+use image::{DynamicImage, ImageDecoder, ImageReader};
 
 use crate::info::{ColourOrder, Mirroring, Rotation};
 use crate::{rgb_to_bgr, Error};
@@ -28,7 +32,7 @@ impl FromStr for Colour {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() != 6 && s.len() != 8 {
-            return Err(format!("Expected colour in the hex form: RRGGBB"));
+            return Err("Expected colour in the hex form: RRGGBB".to_string());
         }
 
         let r =
@@ -42,10 +46,17 @@ impl FromStr for Colour {
     }
 }
 
+impl From<&Colour> for Rgb<u8> {
+    fn from(c: &Colour) -> Self {
+        Rgb([c.r, c.g, c.b])
+    }
+}
+
 /// Options for image loading and editing
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ImageOptions {
     #[cfg_attr(feature = "structopt", structopt(long = "bg"))]
     /// Background colour
@@ -54,20 +65,100 @@ pub struct ImageOptions {
     #[cfg_attr(feature = "structopt", structopt(long))]
     /// Invert colours
     invert: bool,
+
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    /// Gamma correction, values above 1.0 darken and below 1.0 brighten mid-tones
+    gamma: Option<f32>,
+
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "no-auto-orient", parse(from_flag = std::ops::Not::not))
+    )]
+    /// Disable rotation of loaded images using EXIF orientation metadata
+    auto_orient: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self::new(None, false)
+    }
 }
 
 impl ImageOptions {
     pub fn new(background: Option<Colour>, invert: bool) -> Self {
-        ImageOptions { background, invert }
+        ImageOptions {
+            background,
+            invert,
+            gamma: None,
+            auto_orient: true,
+        }
+    }
+
+    /// Set gamma correction to be applied to pixel values
+    pub fn with_gamma(mut self, gamma: Option<f32>) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Set whether images loaded from files are rotated using EXIF orientation
+    /// metadata (enabled by default)
+    pub fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Create a builder for image options, starting from the defaults
+    pub fn builder() -> ImageOptionsBuilder {
+        ImageOptionsBuilder::default()
     }
 }
 
-impl Default for ImageOptions {
-    fn default() -> Self {
-        Self {
-            background: None,
-            invert: false,
-        }
+/// Builder for [ImageOptions], see [ImageOptions::builder]
+#[derive(Debug, Clone, Default)]
+pub struct ImageOptionsBuilder {
+    opts: ImageOptions,
+}
+
+impl ImageOptionsBuilder {
+    /// Set the background colour, replacing transparent regions of the image
+    pub fn background(mut self, background: Colour) -> Self {
+        self.opts.background = Some(background);
+        self
+    }
+
+    /// Set whether image colours are inverted
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.opts.invert = invert;
+        self
+    }
+
+    /// Set gamma correction to be applied to pixel values
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.opts.gamma = Some(gamma);
+        self
+    }
+
+    /// Set whether images loaded from files are rotated using EXIF orientation metadata
+    pub fn auto_orient(mut self, auto_orient: bool) -> Self {
+        self.opts.auto_orient = auto_orient;
+        self
+    }
+
+    /// Build the image options
+    pub fn build(self) -> ImageOptions {
+        self.opts
+    }
+}
+
+/// Apply a gamma curve to pixel values, `out = (in / 255) ^ gamma * 255`
+pub(crate) fn apply_gamma(data: &mut [u8], gamma: f32) {
+    let mut lut = [0u8; 256];
+    for (i, v) in lut.iter_mut().enumerate() {
+        *v = ((i as f32 / 255.0).powf(gamma) * 255.0).round() as u8;
+    }
+
+    for p in data.iter_mut() {
+        *p = lut[*p as usize];
     }
 }
 
@@ -82,41 +173,134 @@ pub(crate) fn apply_transform(
         Rotation::Rot180 => image.rotate180(),
         Rotation::Rot270 => image.rotate270(),
     };
-    let image = match mirroring {
+    match mirroring {
         Mirroring::None => image,
         Mirroring::X => image.flipv(),
         Mirroring::Y => image.fliph(),
         Mirroring::Both => image.flipv().fliph(),
+    }
+}
+
+/// Invert a transform for `apply_transform`, such that applying the transform
+/// followed by the inverse returns the original image
+pub(crate) fn invert_transform(
+    (rotation, mirroring): (Rotation, Mirroring),
+) -> (Rotation, Mirroring) {
+    let rotation = match rotation {
+        Rotation::Rot0 => Rotation::Rot0,
+        Rotation::Rot90 => Rotation::Rot270,
+        Rotation::Rot180 => Rotation::Rot180,
+        Rotation::Rot270 => Rotation::Rot90,
     };
-    image
+    // Mirroring is its own inverse, and must be undone prior to rotation
+    compose_transform((Rotation::Rot0, mirroring), (rotation, Mirroring::None))
+}
+
+/// Compose two transforms into a single transform for `apply_transform`,
+/// equivalent to applying `first` then `second`
+pub(crate) fn compose_transform(
+    first: (Rotation, Mirroring),
+    second: (Rotation, Mirroring),
+) -> (Rotation, Mirroring) {
+    // Transforms are represented as a vertical flip (f) following a number of
+    // clockwise quarter turns (r), where rotation then flipping is equivalent
+    // to flipping then rotating in the opposite direction
+    let normalise = |(r, m): (Rotation, Mirroring)| {
+        let r = match r {
+            Rotation::Rot0 => 0,
+            Rotation::Rot90 => 1,
+            Rotation::Rot180 => 2,
+            Rotation::Rot270 => 3,
+        };
+        match m {
+            Mirroring::None => (r, false),
+            Mirroring::X => (r, true),
+            Mirroring::Y => (r + 2, true),
+            Mirroring::Both => (r + 2, false),
+        }
+    };
+
+    let (r1, f1) = normalise(first);
+    let (r2, f2) = normalise(second);
+
+    let r = match f1 {
+        true => r1 + 4 - r2 % 4,
+        false => r1 + r2,
+    };
+    let rotation = match r % 4 {
+        0 => Rotation::Rot0,
+        1 => Rotation::Rot90,
+        2 => Rotation::Rot180,
+        _ => Rotation::Rot270,
+    };
+    let mirroring = match f1 ^ f2 {
+        true => Mirroring::X,
+        false => Mirroring::None,
+    };
+
+    (rotation, mirroring)
 }
 
 /// Load an image from a file, resize to defined x and y, and apply the provided options
-pub(crate) fn load_image(
-    path: &str,
+///
+/// The format is detected from the file extension, see [crate::StreamDeck::load_image]
+/// for supported formats. Where enabled in the options EXIF orientation is applied
+/// prior to other transforms.
+pub(crate) fn load_image<P: AsRef<Path>>(
+    path: P,
     x: usize,
     y: usize,
-    rotate: Rotation,
-    mirror: Mirroring,
+    transform: (Rotation, Mirroring),
     opts: &ImageOptions,
+    filter: Option<&ImageFilter>,
     colour_order: ColourOrder,
 ) -> Result<Vec<u8>, Error> {
     // Open image reader
-    let reader = match Reader::open(path) {
+    let path = path.as_ref();
+    let reader = match ImageReader::open(path) {
         Ok(v) => v,
         Err(e) => {
-            error!("error loading file '{}': {:?}", path, e);
+            error!("error loading file '{}': {:?}", path.display(), e);
             return Err(Error::Io(e));
         }
     };
 
-    // Load image
-    let mut image = reader.decode().map_err(Error::Image)?;
+    // Load image, applying EXIF orientation if enabled
+    let mut decoder = reader.into_decoder().map_err(Error::Image)?;
+    let orientation = match opts.auto_orient {
+        true => decoder.orientation().map_err(Error::Image)?,
+        false => Orientation::NoTransforms,
+    };
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(Error::Image)?;
+    image.apply_orientation(orientation);
 
+    process_image(image, x, y, transform, opts, filter, colour_order)
+}
+
+/// Custom image filter, applied to resized images prior to device transforms,
+/// see [crate::StreamDeck::set_image_filter]
+pub type ImageFilter = Box<dyn Fn(&mut RgbImage) + Send + Sync>;
+
+/// Resize an image to defined x and y (after rotation), and apply the provided options
+/// and filter
+///
+/// The output length is not checked here, this is validated once on conversion
+/// to the device format (see [crate::Kind::image_size_bytes])
+pub(crate) fn process_image(
+    mut image: DynamicImage,
+    x: usize,
+    y: usize,
+    (rotate, mirror): (Rotation, Mirroring),
+    opts: &ImageOptions,
+    filter: Option<&ImageFilter>,
+    colour_order: ColourOrder,
+) -> Result<Vec<u8>, Error> {
     // Apply background filter / replace
     // This must be done before transparency is removed
     if let Some(c) = &opts.background {
-        let rgba = image.as_mut_rgba8().unwrap();
+        // Convert other formats (ie. RGB frames) to RGBA, this is a no-op for RGBA images
+        let mut rgba = image.into_rgba8();
 
         let mut r = Rgba([c.r, c.g, c.b, 0]);
         if opts.invert {
@@ -128,10 +312,27 @@ pub(crate) fn load_image(
 
             p.blend(&r);
         }
+
+        image = DynamicImage::ImageRgba8(rgba);
     }
 
-    // Resize image
-    let mut image = image.resize(x as u32, y as u32, FilterType::Gaussian);
+    // Resize image so the output is x by y following rotation,
+    // skipping this where the image is already the correct size
+    let (w, h) = match rotate {
+        Rotation::Rot90 | Rotation::Rot270 => (y as u32, x as u32),
+        Rotation::Rot0 | Rotation::Rot180 => (x as u32, y as u32),
+    };
+    let mut image = match image.width() == w && image.height() == h {
+        true => image,
+        false => image.resize(w, h, FilterType::Gaussian),
+    };
+
+    // Apply the custom filter while the image is upright
+    if let Some(f) = filter {
+        let mut rgb = image.into_rgb8();
+        f(&mut rgb);
+        image = DynamicImage::ImageRgb8(rgb);
+    }
 
     // Apply the requested mirroring transformation
     image = apply_transform(image, rotate, mirror);
@@ -143,25 +344,65 @@ pub(crate) fn load_image(
 
     // Convert to vector with correct encoding
     let mut v = image.to_rgb8().into_vec();
-    if matches!(colour_order, ColourOrder::BGR) {
-        rgb_to_bgr(&mut v);
+    if let Some(g) = opts.gamma {
+        apply_gamma(&mut v, g);
     }
-
-    if v.len() != x * y * 3 {
-        return Err(Error::InvalidImageSize);
+    if matches!(colour_order, ColourOrder::Bgr) {
+        rgb_to_bgr(&mut v);
     }
 
     Ok(v)
 }
 
-/// Encodes a BGR bitmap into a JPEG image for outputting to a V2 device
-pub(crate) fn encode_jpeg(image: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+/// Encodes an RGB bitmap into a JPEG image for outputting to a V2 device
+///
+/// This uses the `image` crate encoder by default, or the mozjpeg encoder
+/// when the `mozjpeg` feature is enabled
+///
+/// Returns `Error::InvalidImageSize` if the image is not `width * height` RGB pixels
+#[cfg(not(feature = "mozjpeg"))]
+pub fn encode_jpeg(image: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    // The encoder panics on mis-sized buffers
+    if image.len() != width * height * 3 {
+        return Err(Error::InvalidImageSize);
+    }
+
     let mut buf = Vec::new();
     let mut encoder = JpegEncoder::new_with_quality(&mut buf, 100);
     encoder.encode(image, width as u32, height as u32, ExtendedColorType::Rgb8)?;
     Ok(buf)
 }
 
+/// Encodes an RGB bitmap into a JPEG image for outputting to a V2 device
+///
+/// This uses the `image` crate encoder by default, or the mozjpeg encoder
+/// when the `mozjpeg` feature is enabled
+///
+/// Returns `Error::InvalidImageSize` if the image is not `width * height` RGB pixels
+#[cfg(feature = "mozjpeg")]
+pub fn encode_jpeg(image: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    if image.len() != width * height * 3 {
+        return Err(Error::InvalidImageSize);
+    }
+
+    // mozjpeg reports libjpeg errors by unwinding, so these are caught and
+    // returned as IO errors
+    let res = std::panic::catch_unwind(|| -> std::io::Result<Vec<u8>> {
+        let mut compress = mozjpeg::Compress::new(mozjpeg::ColorSpace::JCS_RGB);
+        compress.set_size(width, height);
+        compress.set_quality(100.0);
+
+        let mut started = compress.start_compress(Vec::new())?;
+        started.write_scanlines(image)?;
+        started.finish()
+    });
+
+    match res {
+        Ok(r) => r.map_err(Error::Io),
+        Err(_) => Err(Error::Io(std::io::Error::other("mozjpeg encoding failed"))),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -172,11 +413,169 @@ mod test {
             "./icons/power.png",
             72,
             72,
-            Rotation::Rot180,
-            Mirroring::Both,
+            (Rotation::Rot180, Mirroring::Both),
             &ImageOptions::default(),
-            ColourOrder::BGR,
+            None,
+            ColourOrder::Bgr,
         )
         .expect("error loading image");
     }
+
+    #[test]
+    fn rotated_output_size() {
+        let opts = ImageOptions::default();
+
+        // Output is 30x20 after rotation, so the source is 20x30 when rotated
+        for (rotation, (w, h)) in [(Rotation::Rot0, (30, 20)), (Rotation::Rot90, (20, 30))] {
+            let v = process_image(
+                DynamicImage::new_rgb8(w, h),
+                30,
+                20,
+                (rotation, Mirroring::None),
+                &opts,
+                None,
+                ColourOrder::Rgb,
+            )
+            .unwrap();
+            assert_eq!(v.len(), 30 * 20 * 3, "{:?}", rotation);
+        }
+    }
+
+    #[test]
+    fn exif_orientation() {
+        // 2x1 image, with an EXIF orientation of 90 degrees clockwise
+        let image = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([x as u8 * 255; 3]));
+        let mut jpeg = std::io::Cursor::new(vec![]);
+        image.write_to(&mut jpeg, image::ImageFormat::Jpeg).unwrap();
+        let mut jpeg = jpeg.into_inner();
+
+        let tiff = [
+            0x49, 0x49, 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, // header
+            0x01, 0x00, // entry count
+            0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, // orientation tag
+            0x06, 0x00, 0x00, 0x00, // rotate 90 degrees
+            0x00, 0x00, 0x00, 0x00, // next IFD
+        ];
+        let mut app1 = vec![0xff, 0xe1];
+        app1.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+        jpeg.splice(2..2, app1);
+
+        let path = std::env::temp_dir().join("streamdeck-exif-orientation.jpg");
+        std::fs::write(&path, &jpeg).unwrap();
+        let load = |opts: &ImageOptions| {
+            let transform = (Rotation::Rot0, Mirroring::None);
+            load_image(&path, 1, 2, transform, opts, None, ColourOrder::Rgb).unwrap()
+        };
+
+        // Rotated to portrait, with the dark pixel at the top
+        let v = load(&ImageOptions::default());
+        assert!(v[0] < 50 && v[3] > 200, "{:?}", v);
+
+        // Without orientation the image is resized (preserving aspect ratio) and mismatched
+        let v = load(&ImageOptions::default().with_auto_orient(false));
+        assert_ne!(v.len(), 2 * 3);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn rgb_background() {
+        let red = Colour { r: 255, g: 0, b: 0 };
+        let opts = ImageOptions::new(Some(red), false);
+
+        // Opaque RGB images are unchanged by the background
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])));
+        let transform = (Rotation::Rot0, Mirroring::None);
+        let v = process_image(image, 4, 4, transform, &opts, None, ColourOrder::Rgb).unwrap();
+        assert_eq!(&v[..3], &[0, 0, 255]);
+
+        // Transparent regions of RGBA images are replaced
+        let image = DynamicImage::new_rgba8(4, 4);
+        let v = process_image(image, 4, 4, transform, &opts, None, ColourOrder::Rgb).unwrap();
+        assert_eq!(&v[..3], &[255, 0, 0]);
+    }
+
+    #[test]
+    fn gamma_curve() {
+        let mut data = [0, 64, 128, 255];
+        apply_gamma(&mut data, 1.0);
+        assert_eq!(data, [0, 64, 128, 255]);
+
+        apply_gamma(&mut data, 2.0);
+        assert_eq!(data, [0, 16, 64, 255]);
+    }
+
+    #[test]
+    fn encode_jpegs() {
+        let image = vec![0x80; 72 * 72 * 3];
+        let jpeg = encode_jpeg(&image, 72, 72).expect("error encoding image");
+
+        assert_eq!(&jpeg[..2], &[0xff, 0xd8]);
+        assert_eq!(&jpeg[jpeg.len() - 2..], &[0xff, 0xd9]);
+
+        assert!(matches!(
+            encode_jpeg(&image[1..], 72, 72),
+            Err(Error::InvalidImageSize)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn load_webp() {
+        let _image = load_image(
+            "./icons/power.webp",
+            72,
+            72,
+            (Rotation::Rot0, Mirroring::None),
+            &ImageOptions::default(),
+            None,
+            ColourOrder::Rgb,
+        )
+        .expect("error loading image");
+    }
+
+    #[test]
+    fn compose_transforms() {
+        let rotations = [
+            Rotation::Rot0,
+            Rotation::Rot90,
+            Rotation::Rot180,
+            Rotation::Rot270,
+        ];
+        let mirrors = [Mirroring::None, Mirroring::X, Mirroring::Y, Mirroring::Both];
+
+        // Non-square image with distinct pixels
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_fn(2, 3, |x, y| {
+            image::Luma([(y * 2 + x) as u8])
+        }));
+
+        for &r1 in &rotations {
+            for &m1 in &mirrors {
+                for &r2 in &rotations {
+                    for &m2 in &mirrors {
+                        let expected =
+                            apply_transform(apply_transform(image.clone(), r1, m1), r2, m2);
+                        let (r, m) = compose_transform((r1, m1), (r2, m2));
+                        let actual = apply_transform(image.clone(), r, m);
+
+                        let (ri, mi) = invert_transform((r, m));
+                        let inverse = apply_transform(actual.clone(), ri, mi);
+                        assert_eq!(image.as_bytes(), inverse.as_bytes());
+
+                        assert_eq!(
+                            expected.as_bytes(),
+                            actual.as_bytes(),
+                            "{:?} {:?} {:?} {:?}",
+                            r1,
+                            m1,
+                            r2,
+                            m2
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,5 +1,60 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::images::encode_jpeg;
+use crate::{Colour, Error};
+
+/// Device firmware version, parsed from the dotted version string
+/// (ie. `1.01.000`) for comparison
+///
+/// Missing components are treated as zero, so `1.01` equals `1.1.0`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl FirmwareVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+
+        let mut parts = [0u16; 3];
+        for (i, p) in s.split('.').enumerate() {
+            let v = parts
+                .get_mut(i)
+                .ok_or_else(|| format!("Too many version components in '{}'", s))?;
+            *v = p
+                .parse()
+                .map_err(|e| format!("Invalid version component '{}': {}", p, e))?;
+        }
+
+        Ok(Self::new(parts[0], parts[1], parts[2]))
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
 /// Stream Deck Device Kinds
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum Kind {
     Original,
     OriginalV2,
@@ -27,12 +82,12 @@ pub enum ImageMode {
 /// Stream Deck color mode
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum ColourOrder {
-    RGB,
-    BGR,
+    Rgb,
+    Bgr,
 }
 
 /// Rotation to apply to an image
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Rotation {
     Rot0,
     Rot90,
@@ -41,7 +96,7 @@ pub enum Rotation {
 }
 
 /// Mirroring to apply to an image
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Mirroring {
     None,
     X,
@@ -59,6 +114,11 @@ impl Kind {
         }
     }
 
+    /// Check whether a zero-indexed key index exists on this kind (`0..keys()`)
+    pub fn is_valid_key(&self, index: u8) -> bool {
+        index < self.keys()
+    }
+
     // Offset for the first key in button report
     pub(crate) fn key_data_offset(&self) -> usize {
         match self {
@@ -84,7 +144,7 @@ impl Kind {
         }
     }
 
-    pub(crate) fn key_columns(&self) -> u8 {
+    pub fn key_columns(&self) -> u8 {
         match self {
             Kind::Mini | Kind::RevisedMini => 3,
             Kind::Original | Kind::OriginalV2 | Kind::Mk2 => 5,
@@ -93,6 +153,10 @@ impl Kind {
         }
     }
 
+    pub fn key_rows(&self) -> u8 {
+        self.keys() / self.key_columns()
+    }
+
     pub fn image_mode(&self) -> ImageMode {
         match self {
             Kind::Original | Kind::Mini | Kind::RevisedMini => ImageMode::Bmp,
@@ -109,6 +173,44 @@ impl Kind {
         }
     }
 
+    /// Approximate gap between keys in pixels (at the key image scale),
+    /// for laying out images across multiple keys
+    pub fn key_gap(&self) -> usize {
+        match self {
+            Kind::Original | Kind::OriginalV2 | Kind::Mk2 => 36,
+            Kind::Mini | Kind::RevisedMini => 40,
+            Kind::Xl => 40,
+            Kind::Plus => 50,
+        }
+    }
+
+    /// Pixel rectangle `(x, y, w, h)` of a key within a virtual canvas spanning
+    /// all keys (see `canvas_size`), accounting for the gap between keys
+    ///
+    /// Returns `None` for invalid key indices
+    pub fn key_rect(&self, index: u8) -> Option<(usize, usize, usize, usize)> {
+        if !self.is_valid_key(index) {
+            return None;
+        }
+
+        let (w, h) = self.image_size();
+        let gap = self.key_gap();
+        let row = (index / self.key_columns()) as usize;
+        let col = (index % self.key_columns()) as usize;
+
+        Some((col * (w + gap), row * (h + gap), w, h))
+    }
+
+    /// Size of a virtual canvas spanning all keys, see `key_rect`
+    pub fn canvas_size(&self) -> (usize, usize) {
+        let (w, h) = self.image_size();
+        let gap = self.key_gap();
+        let columns = self.key_columns() as usize;
+        let rows = self.key_rows() as usize;
+
+        (columns * (w + gap) - gap, rows * (h + gap) - gap)
+    }
+
     pub fn image_rotation(&self) -> Rotation {
         match self {
             Kind::Mini | Kind::RevisedMini => Rotation::Rot270,
@@ -132,20 +234,90 @@ impl Kind {
         x * y * 3
     }
 
-    pub(crate) fn image_report_len(&self) -> usize {
+    /// Encode pixel data into the device image format, as written to keys
+    ///
+    /// `pixels` must be in the device colour order and orientation, see
+    /// `StreamDeck::write_button_image_raw`. JPEG devices use [encode_jpeg],
+    /// while BMP devices take the pixel data as-is. Output matches that
+    /// of the crate when built with the same features (ie. `mozjpeg`).
+    ///
+    /// Returns `Error::InvalidImageSize` if the data is incorrectly sized.
+    pub fn encode_image(&self, pixels: &[u8]) -> Result<Vec<u8>, Error> {
+        if pixels.len() != self.image_size_bytes() {
+            return Err(Error::InvalidImageSize);
+        }
+
+        match self.image_mode() {
+            ImageMode::Bmp => Ok(pixels.to_vec()),
+            ImageMode::Jpeg => {
+                let (w, h) = self.image_size();
+                encode_jpeg(pixels, w, h)
+            }
+        }
+    }
+
+    /// Length of input reports (including the report ID) in bytes
+    ///
+    /// This covers the button states for each kind, and the largest
+    /// (touchscreen) report on the Stream Deck Plus
+    pub fn input_report_len(&self) -> usize {
+        match self {
+            Kind::Original => 16,
+            Kind::OriginalV2 | Kind::Mk2 => 19,
+            Kind::Mini => 7,
+            Kind::RevisedMini => 8,
+            Kind::Xl => 36,
+            Kind::Plus => 14,
+        }
+    }
+
+    /// Length of image output reports (including the header) in bytes
+    pub fn image_report_len(&self) -> usize {
         match self {
             Kind::Original => 8191,
             _ => 1024,
         }
     }
 
-    pub(crate) fn image_report_header_len(&self) -> usize {
+    /// Length of the header at the start of each image output report in bytes
+    pub fn image_report_header_len(&self) -> usize {
         match self {
             Kind::Original | Kind::Mini | Kind::RevisedMini => 16,
             Kind::OriginalV2 | Kind::Xl | Kind::Mk2 | Kind::Plus => 8,
         }
     }
 
+    /// Number of dials, dial events are indexed from `0..dial_count()`
+    pub fn dial_count(&self) -> u8 {
+        match self {
+            Kind::Plus => 4,
+            _ => 0,
+        }
+    }
+
+    /// Whether this kind has dials
+    pub fn has_dials(&self) -> bool {
+        self.dial_count() > 0
+    }
+
+    /// Touchscreen (or info bar) resolution in pixels, `None` for kinds without one
+    pub fn touchscreen_size(&self) -> Option<(usize, usize)> {
+        match self {
+            Kind::Plus => Some((800, 100)),
+            _ => None,
+        }
+    }
+
+    /// Length of feature reports (including the report ID) in bytes
+    pub fn feature_report_len(&self) -> usize {
+        match self.is_v2() {
+            true => 32,
+            false => 17,
+        }
+    }
+
+    /// Header (ie. BMP) prepended to image data in the first image report,
+    /// empty for devices that do not require one
     pub fn image_base(&self) -> &[u8] {
         match self {
             // BMP headers for the original and mini
@@ -156,21 +328,50 @@ impl Kind {
         }
     }
 
+    /// Build a solid colour image of the device image size, in device colour order
+    pub fn colour_image(&self, colour: &Colour) -> Vec<u8> {
+        let pixel = match self.image_colour_order() {
+            ColourOrder::Bgr => [colour.b, colour.g, colour.r],
+            ColourOrder::Rgb => [colour.r, colour.g, colour.b],
+        };
+
+        let mut image = vec![0u8; self.image_size_bytes()];
+        for p in image.chunks_exact_mut(3) {
+            p.copy_from_slice(&pixel);
+        }
+        image
+    }
+
     pub(crate) fn image_colour_order(&self) -> ColourOrder {
         match self {
-            Kind::Original | Kind::Mini | Kind::RevisedMini => ColourOrder::BGR,
-            Kind::OriginalV2 | Kind::Xl | Kind::Mk2 | Kind::Plus => ColourOrder::RGB,
+            Kind::Original | Kind::Mini | Kind::RevisedMini => ColourOrder::Bgr,
+            Kind::OriginalV2 | Kind::Xl | Kind::Mk2 | Kind::Plus => ColourOrder::Rgb,
         }
     }
 
-    pub(crate) fn is_v2(&self) -> bool {
+    /// Whether this kind has a display (key images and / or a screen),
+    /// screenless models do not support brightness, reset or image commands
+    ///
+    /// All currently supported kinds have a display, this is not public
+    /// until a screenless kind is added.
+    pub(crate) fn has_display(&self) -> bool {
         match self {
-            Kind::OriginalV2 | Kind::Xl | Kind::Mk2 | Kind::Plus => true,
-            _ => false,
+            Kind::Original
+            | Kind::OriginalV2
+            | Kind::Mini
+            | Kind::RevisedMini
+            | Kind::Xl
+            | Kind::Mk2
+            | Kind::Plus => true,
         }
     }
+
+    pub(crate) fn is_v2(&self) -> bool {
+        matches!(self, Kind::OriginalV2 | Kind::Xl | Kind::Mk2 | Kind::Plus)
+    }
 }
 
+// 72x72 24-bit BMP header
 pub const ORIGINAL_IMAGE_BASE: [u8; 54] = [
     0x42, 0x4d, 0xf6, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x00, 0x00, 0x00, 0x28, 0x00,
     0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 0x00, 0x00,
@@ -178,9 +379,10 @@ pub const ORIGINAL_IMAGE_BASE: [u8; 54] = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
+// 80x80 24-bit BMP header
 const MINI_IMAGE_BASE: [u8; 54] = [
-    0x42, 0x4d, 0xf6, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x00, 0x00, 0x00, 0x28, 0x00,
-    0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0xc0, 0x3c, 0x00, 0x00, 0xc4, 0x0e, 0x00, 0x00, 0xc4, 0x0e, 0x00, 0x00, 0x00, 0x00,
+    0x42, 0x4d, 0x36, 0x4b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x00, 0x00, 0x00, 0x28, 0x00,
+    0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x4b, 0x00, 0x00, 0xc4, 0x0e, 0x00, 0x00, 0xc4, 0x0e, 0x00, 0x00, 0x00, 0x00,
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
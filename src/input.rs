@@ -0,0 +1,641 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Kind, SharedStreamDeck, StreamDeck};
+
+/// Decoded input events, see [InputManager]
+///
+/// With the `serde` feature enabled these are serialised with a `type` tag,
+/// ie. `{"type":"ButtonPress","index":3,"row":0,"col":3}`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type")
+)]
+pub enum InputEvent {
+    /// Button has been pressed, with the linear key index and zero-indexed
+    /// (row, column) position, see `StreamDeck::key_index`
+    ButtonPress { index: u8, row: u8, col: u8 },
+    /// Button has been released
+    ButtonRelease { index: u8, row: u8, col: u8 },
+    /// Button has been held for longer than the hold threshold,
+    /// see [InputManager::with_hold_threshold]
+    ButtonHold { index: u8, row: u8, col: u8 },
+    /// Dial has been pressed (Stream Deck Plus only),
+    /// dial indices are in the range `0..Kind::dial_count()`
+    DialPress { index: u8 },
+    /// Dial has been released (Stream Deck Plus only)
+    DialRelease { index: u8 },
+    /// Dial has been turned by the provided number of steps,
+    /// positive values are clockwise (Stream Deck Plus only)
+    DialTurn { index: u8, delta: i8 },
+    /// Short touch at the provided coordinates (Stream Deck Plus only)
+    TouchShort { x: u16, y: u16 },
+    /// Long touch at the provided coordinates (Stream Deck Plus only)
+    TouchLong { x: u16, y: u16 },
+    /// Drag between the provided coordinates (Stream Deck Plus only)
+    TouchDrag { from: (u16, u16), to: (u16, u16) },
+    /// Touchscreen button region has been tapped (Stream Deck Plus only),
+    /// see `StreamDeck::set_touch_button_image`. As taps are
+    /// instantaneous each is reported as a press followed by a release.
+    TouchButton { index: u8, pressed: bool },
+    /// Unrecognised input report, containing the raw report bytes
+    /// (including the report ID) for debugging or decoding new devices
+    Unknown { report: Vec<u8> },
+}
+
+impl std::fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputEvent::ButtonPress { index, .. } => write!(f, "button {} pressed", index),
+            InputEvent::ButtonRelease { index, .. } => write!(f, "button {} released", index),
+            InputEvent::ButtonHold { index, .. } => write!(f, "button {} held", index),
+            InputEvent::DialPress { index } => write!(f, "dial {} pressed", index),
+            InputEvent::DialRelease { index } => write!(f, "dial {} released", index),
+            InputEvent::DialTurn { index, delta } => write!(f, "dial {} turned {:+}", index, delta),
+            InputEvent::TouchShort { x, y } => write!(f, "short touch at ({}, {})", x, y),
+            InputEvent::TouchLong { x, y } => write!(f, "long touch at ({}, {})", x, y),
+            InputEvent::TouchDrag { from, to } => write!(
+                f,
+                "drag from ({}, {}) to ({}, {})",
+                from.0, from.1, to.0, to.1
+            ),
+            InputEvent::TouchButton { index, pressed } => match pressed {
+                true => write!(f, "touch button {} pressed", index),
+                false => write!(f, "touch button {} released", index),
+            },
+            InputEvent::Unknown { report } => write!(f, "unknown report {:02x?}", report),
+        }
+    }
+}
+
+/// Input report types (Stream Deck Plus)
+mod report {
+    pub const BUTTON: u8 = 0x00;
+    pub const TOUCH: u8 = 0x02;
+    pub const DIAL: u8 = 0x03;
+}
+
+/// Touch event types (Stream Deck Plus)
+mod touch {
+    pub const SHORT: u8 = 0x01;
+    pub const LONG: u8 = 0x02;
+    pub const DRAG: u8 = 0x03;
+}
+
+/// Dial event types (Stream Deck Plus)
+mod dial {
+    pub const PRESS: u8 = 0x00;
+    pub const TURN: u8 = 0x01;
+}
+
+/// Number of touchscreen button regions on the Stream Deck Plus, one above each dial
+pub const TOUCH_BUTTONS: u8 = 4;
+
+/// Default threshold for button hold events
+pub const DEFAULT_HOLD_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// Input manager, decodes input reports into [InputEvent]s
+///
+/// This tracks button and dial states so that presses and releases
+/// can be reported as discrete events.
+///
+/// The device is borrowed for the lifetime of the manager, see [InputState]
+/// to decode input while using the device elsewhere.
+pub struct InputManager<'a> {
+    deck: &'a mut StreamDeck,
+    state: InputState,
+}
+
+impl<'a> InputManager<'a> {
+    /// Create an input manager for the provided device
+    pub fn new(deck: &'a mut StreamDeck) -> Self {
+        let state = InputState::new(deck.kind());
+        Self { deck, state }
+    }
+
+    /// Set the duration a button must be held before a
+    /// [InputEvent::ButtonHold] is emitted (defaults to [DEFAULT_HOLD_THRESHOLD])
+    ///
+    /// Note that long and short touches on the Stream Deck Plus touchscreen
+    /// are classified by the device firmware and are not affected by this.
+    pub fn with_hold_threshold(mut self, threshold: Duration) -> Self {
+        self.state = self.state.with_hold_threshold(threshold);
+        self
+    }
+
+    /// Read and decode an input report
+    ///
+    /// See `StreamDeck::read_buttons` for discussion of blocking and timeouts.
+    /// Returns an empty list if no report was available.
+    ///
+    /// Button hold events are generated when this is called, so this must be
+    /// polled while buttons are held for these events to be emitted.
+    pub fn handle_input(&mut self, timeout: Option<Duration>) -> Result<Vec<InputEvent>, Error> {
+        Ok(self.poll(timeout)?.unwrap_or_default())
+    }
+
+    /// Read and decode all currently available input reports without blocking,
+    /// returning events in the order received
+    ///
+    /// This avoids falling behind during bursts of input where reports are read
+    /// less often than they are generated (ie. once per frame).
+    pub fn drain_input(&mut self) -> Result<Vec<InputEvent>, Error> {
+        self.state.drain_input(self.deck)
+    }
+
+    /// Fetch an iterator over input events
+    ///
+    /// This repeatedly calls `handle_input`, ending when no report is
+    /// received within the timeout or on error (see [InputEventsIter::take_error])
+    pub fn events(&mut self, timeout: Option<Duration>) -> InputEventsIter<'_, 'a> {
+        InputEventsIter {
+            manager: self,
+            timeout,
+            pending: VecDeque::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Read and decode an input report, returning `None` if no report was available
+    fn poll(&mut self, timeout: Option<Duration>) -> Result<Option<Vec<InputEvent>>, Error> {
+        self.state.poll(self.deck, timeout)
+    }
+}
+
+/// Button and dial state tracking for input decoding, independent of the device borrow
+///
+/// This provides the same decoding as [InputManager] with the device passed to each
+/// call, so keys may be updated in response to input from the same owner, or
+/// input read on one thread while keys are updated on another via a [SharedStreamDeck].
+pub struct InputState {
+    buttons: Vec<u8>,
+    columns: u8,
+    dials: Vec<u8>,
+
+    hold_threshold: Duration,
+    pressed_at: Vec<Option<Instant>>,
+}
+
+impl InputState {
+    /// Create input state tracking for the provided device kind
+    pub fn new(kind: Kind) -> Self {
+        let keys = kind.keys() as usize;
+        Self {
+            buttons: vec![0u8; keys],
+            columns: kind.key_columns(),
+            dials: vec![0u8; kind.dial_count() as usize],
+            hold_threshold: DEFAULT_HOLD_THRESHOLD,
+            pressed_at: vec![None; keys],
+        }
+    }
+
+    /// Set the duration a button must be held before a [InputEvent::ButtonHold]
+    /// is emitted, see [InputManager::with_hold_threshold]
+    pub fn with_hold_threshold(mut self, threshold: Duration) -> Self {
+        self.hold_threshold = threshold;
+        self
+    }
+
+    /// Read and decode an input report, see [InputManager::handle_input]
+    pub fn handle_input(
+        &mut self,
+        deck: &mut StreamDeck,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<InputEvent>, Error> {
+        Ok(self.poll(deck, timeout)?.unwrap_or_default())
+    }
+
+    /// Read and decode all currently available input reports, see [InputManager::drain_input]
+    pub fn drain_input(&mut self, deck: &mut StreamDeck) -> Result<Vec<InputEvent>, Error> {
+        let mut events = vec![];
+        while let Some(e) = self.poll(deck, Some(Duration::ZERO))? {
+            events.extend(e);
+        }
+        Ok(events)
+    }
+
+    /// Read and decode an input report from a shared device
+    ///
+    /// The device is locked only while reading, a timeout should be provided
+    /// so that other users of the device are not blocked waiting for input.
+    pub fn handle_shared(
+        &mut self,
+        deck: &SharedStreamDeck,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<InputEvent>, Error> {
+        deck.with(|d| self.handle_input(d, timeout))
+    }
+
+    /// Read and decode an input report, returning `None` if no report was available
+    pub(crate) fn poll(
+        &mut self,
+        deck: &mut StreamDeck,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Vec<InputEvent>>, Error> {
+        let (mut events, received) = match deck.read_input_len(timeout) {
+            Ok((cmd, n)) => (self.decode(deck, &cmd[..n])?, true),
+            Err(Error::NoData) => (vec![], false),
+            Err(e) => return Err(e),
+        };
+
+        events.extend(self.check_holds());
+
+        match received || !events.is_empty() {
+            true => Ok(Some(events)),
+            false => Ok(None),
+        }
+    }
+
+    fn decode(&mut self, deck: &StreamDeck, cmd: &[u8]) -> Result<Vec<InputEvent>, Error> {
+        if deck.kind() != Kind::Plus {
+            return self.handle_buttons(deck, cmd);
+        }
+        if cmd.len() < 2 {
+            return Err(Error::ShortReport(cmd.len()));
+        }
+
+        match cmd[1] {
+            report::BUTTON => self.handle_buttons(deck, cmd),
+            report::TOUCH => handle_touch(cmd),
+            report::DIAL => self.handle_dials(cmd),
+            _ => Ok(unknown_report(cmd)),
+        }
+    }
+
+    /// Generate hold events for buttons held past the threshold,
+    /// each hold is only reported once per press
+    fn check_holds(&mut self) -> Vec<InputEvent> {
+        let now = Instant::now();
+        let mut events = vec![];
+
+        let columns = self.columns;
+
+        for (i, p) in self.pressed_at.iter_mut().enumerate() {
+            if let Some(t) = p {
+                if now.duration_since(*t) >= self.hold_threshold {
+                    let index = i as u8;
+                    events.push(InputEvent::ButtonHold {
+                        index,
+                        row: index / columns,
+                        col: index % columns,
+                    });
+                    *p = None;
+                }
+            }
+        }
+
+        events
+    }
+
+    fn handle_buttons(&mut self, deck: &StreamDeck, cmd: &[u8]) -> Result<Vec<InputEvent>, Error> {
+        let buttons = deck.decode_buttons(cmd)?;
+
+        let columns = self.columns;
+        let events = diff_states(&self.buttons, &buttons, |index, pressed| {
+            let (row, col) = (index / columns, index % columns);
+            match pressed {
+                true => InputEvent::ButtonPress { index, row, col },
+                false => InputEvent::ButtonRelease { index, row, col },
+            }
+        });
+
+        // Track press times for hold detection
+        let now = Instant::now();
+        for e in &events {
+            match e {
+                InputEvent::ButtonPress { index, .. } => {
+                    self.pressed_at[*index as usize] = Some(now)
+                }
+                InputEvent::ButtonRelease { index, .. } => self.pressed_at[*index as usize] = None,
+                _ => (),
+            }
+        }
+
+        self.buttons = buttons;
+
+        Ok(events)
+    }
+
+    fn handle_dials(&mut self, cmd: &[u8]) -> Result<Vec<InputEvent>, Error> {
+        let dials = self.dials.len();
+        if cmd.len() < 5 + dials {
+            return Err(Error::ShortReport(cmd.len()));
+        }
+        let values = &cmd[5..5 + dials];
+
+        match cmd[4] {
+            dial::PRESS => {
+                let events = diff_states(&self.dials, values, |index, pressed| match pressed {
+                    true => InputEvent::DialPress { index },
+                    false => InputEvent::DialRelease { index },
+                });
+
+                self.dials = values.to_vec();
+
+                Ok(events)
+            }
+            dial::TURN => {
+                let events = values
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| **v != 0)
+                    .map(|(i, v)| InputEvent::DialTurn {
+                        index: i as u8,
+                        delta: *v as i8,
+                    })
+                    .collect();
+
+                Ok(events)
+            }
+            _ => Ok(unknown_report(cmd)),
+        }
+    }
+}
+
+/// Iterator over input events, see [InputManager::events]
+pub struct InputEventsIter<'m, 'a> {
+    manager: &'m mut InputManager<'a>,
+    timeout: Option<Duration>,
+    pending: VecDeque<InputEvent>,
+    error: Option<Error>,
+    done: bool,
+}
+
+impl<'m, 'a> InputEventsIter<'m, 'a> {
+    /// Take the error which ended iteration, if any
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+}
+
+impl<'m, 'a> Iterator for InputEventsIter<'m, 'a> {
+    type Item = InputEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            match self.manager.poll(self.timeout) {
+                Ok(Some(events)) => self.pending.extend(events),
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.error = Some(e);
+                    self.done = true;
+                }
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+/// Decode a touchscreen report
+fn handle_touch(cmd: &[u8]) -> Result<Vec<InputEvent>, Error> {
+    if cmd.len() < 14 {
+        return Err(Error::ShortReport(cmd.len()));
+    }
+
+    let x = u16::from_le_bytes([cmd[6], cmd[7]]);
+    let y = u16::from_le_bytes([cmd[8], cmd[9]]);
+
+    let event = match cmd[4] {
+        touch::SHORT => InputEvent::TouchShort { x, y },
+        touch::LONG => InputEvent::TouchLong { x, y },
+        touch::DRAG => {
+            let x2 = u16::from_le_bytes([cmd[10], cmd[11]]);
+            let y2 = u16::from_le_bytes([cmd[12], cmd[13]]);
+            InputEvent::TouchDrag {
+                from: (x, y),
+                to: (x2, y2),
+            }
+        }
+        _ => return Ok(unknown_report(cmd)),
+    };
+
+    let mut events = vec![event];
+
+    // Taps within a touch button region are also reported as button events
+    if cmd[4] == touch::SHORT || cmd[4] == touch::LONG {
+        if let Some(index) = touch_button_index(x) {
+            events.push(InputEvent::TouchButton {
+                index,
+                pressed: true,
+            });
+            events.push(InputEvent::TouchButton {
+                index,
+                pressed: false,
+            });
+        }
+    }
+
+    Ok(events)
+}
+
+/// Report an unrecognised input report as an event, for debugging
+fn unknown_report(cmd: &[u8]) -> Vec<InputEvent> {
+    debug!("Unknown input report: {:02x?}", cmd);
+    vec![InputEvent::Unknown {
+        report: cmd.to_vec(),
+    }]
+}
+
+/// Fetch the touch button region containing the provided x coordinate
+fn touch_button_index(x: u16) -> Option<u8> {
+    let (width, _) = Kind::Plus.touchscreen_size()?;
+    let index = x as usize / (width / TOUCH_BUTTONS as usize);
+
+    match index < TOUCH_BUTTONS as usize {
+        true => Some(index as u8),
+        false => None,
+    }
+}
+
+/// Generate press / release events for changed states
+fn diff_states<F>(prev: &[u8], next: &[u8], f: F) -> Vec<InputEvent>
+where
+    F: Fn(u8, bool) -> InputEvent,
+{
+    prev.iter()
+        .zip(next.iter())
+        .enumerate()
+        .filter(|(_, (p, n))| (**p != 0) != (**n != 0))
+        .map(|(i, (_, n))| f(i as u8, *n != 0))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_touch() {
+        let mut cmd = [0u8; 36];
+        cmd[..4].copy_from_slice(&[0x01, report::TOUCH, 0x0e, 0x00]);
+
+        cmd[4] = touch::SHORT;
+        cmd[6..10].copy_from_slice(&[0x2c, 0x01, 0x32, 0x00]);
+        assert_eq!(
+            handle_touch(&cmd).unwrap(),
+            vec![
+                InputEvent::TouchShort { x: 300, y: 50 },
+                InputEvent::TouchButton {
+                    index: 1,
+                    pressed: true
+                },
+                InputEvent::TouchButton {
+                    index: 1,
+                    pressed: false
+                },
+            ]
+        );
+
+        cmd[4] = touch::DRAG;
+        cmd[10..14].copy_from_slice(&[0x58, 0x02, 0x28, 0x00]);
+        assert_eq!(
+            handle_touch(&cmd).unwrap(),
+            vec![InputEvent::TouchDrag {
+                from: (300, 50),
+                to: (600, 40)
+            }]
+        );
+
+        assert!(matches!(
+            handle_touch(&cmd[..10]),
+            Err(Error::ShortReport(10))
+        ));
+    }
+
+    #[test]
+    fn detached_state() {
+        let (mut deck, mock) = crate::test::mock_deck(Kind::Mini);
+        let mut state = InputState::new(deck.kind());
+
+        mock.push_input(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        let events = state.handle_input(&mut deck, None).unwrap();
+        assert_eq!(
+            events,
+            vec![InputEvent::ButtonPress {
+                index: 1,
+                row: 0,
+                col: 1
+            }]
+        );
+
+        // The device remains usable between reads
+        deck.set_button_rgb(1, &crate::Colour { r: 255, g: 0, b: 0 })
+            .unwrap();
+
+        let shared = crate::SharedStreamDeck::new(deck);
+        mock.push_input(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let events = state.handle_shared(&shared, None).unwrap();
+        assert_eq!(
+            events,
+            vec![InputEvent::ButtonRelease {
+                index: 1,
+                row: 0,
+                col: 1
+            }]
+        );
+
+        // Positions are reported by (row, column)
+        mock.push_input(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        let events = state.handle_shared(&shared, None).unwrap();
+        assert_eq!(
+            events,
+            vec![InputEvent::ButtonPress {
+                index: 4,
+                row: 1,
+                col: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn drain_reports() {
+        let (mut deck, mock) = crate::test::mock_deck(Kind::Mini);
+
+        mock.push_input(&[0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        mock.push_input(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        mock.push_input(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+
+        let mut manager = InputManager::new(&mut deck);
+        let events = manager.drain_input().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                InputEvent::ButtonPress {
+                    index: 0,
+                    row: 0,
+                    col: 0
+                },
+                InputEvent::ButtonRelease {
+                    index: 0,
+                    row: 0,
+                    col: 0
+                },
+                InputEvent::ButtonPress {
+                    index: 2,
+                    row: 0,
+                    col: 2
+                },
+            ]
+        );
+
+        assert!(manager.drain_input().unwrap().is_empty());
+    }
+
+    #[test]
+    fn decode_dials() {
+        let mut state = InputState::new(Kind::Plus);
+
+        let mut cmd = [0u8; 36];
+        cmd[..5].copy_from_slice(&[0x01, report::DIAL, 0x05, 0x00, dial::TURN]);
+        cmd[5..9].copy_from_slice(&[0x00, 0x02, 0x00, 0xff]);
+        assert_eq!(
+            state.handle_dials(&cmd).unwrap(),
+            vec![
+                InputEvent::DialTurn { index: 1, delta: 2 },
+                InputEvent::DialTurn {
+                    index: 3,
+                    delta: -1
+                },
+            ]
+        );
+
+        cmd[4] = dial::PRESS;
+        cmd[5..9].copy_from_slice(&[0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(
+            state.handle_dials(&cmd).unwrap(),
+            vec![InputEvent::DialPress { index: 2 }]
+        );
+
+        assert!(matches!(
+            state.handle_dials(&cmd[..8]),
+            Err(Error::ShortReport(8))
+        ));
+
+        cmd[4] = 0x7f;
+        assert_eq!(
+            state.handle_dials(&cmd[..9]).unwrap(),
+            vec![InputEvent::Unknown {
+                report: cmd[..9].to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialise_events() {
+        let e = InputEvent::ButtonPress {
+            index: 3,
+            row: 0,
+            col: 3,
+        };
+        let s = serde_json::to_string(&e).unwrap();
+        assert_eq!(s, r#"{"type":"ButtonPress","index":3,"row":0,"col":3}"#);
+
+        let d: InputEvent = serde_json::from_str(&s).unwrap();
+        assert_eq!(d, e);
+    }
+}
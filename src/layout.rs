@@ -0,0 +1,158 @@
+use std::io::{Error as IoError, ErrorKind};
+
+use ab_glyph::{FontRef, PxScale};
+use serde::{Deserialize, Serialize};
+
+use crate::{Colour, Error, ImageOptions, StreamDeck, TextOptions, TextPosition};
+
+/// Deck layout, describing brightness and per-key content
+///
+/// This is intended to be loaded from a configuration file, for example (in JSON):
+/// ```json
+/// {
+///   "brightness": 80,
+///   "font": "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+///   "keys": [
+///     { "key": 0, "colour": { "r": 255, "g": 0, "b": 0 } },
+///     { "key": 1, "image": "icons/power.png" },
+///     { "key": 2, "text": "hello" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Layout {
+    /// Display brightness (in percent), values above 100 are rejected on `apply`
+    pub brightness: Option<u8>,
+    /// Font file used for text keys
+    pub font: Option<String>,
+    /// Text scale (height in pixels) for text keys
+    pub text_scale: Option<f32>,
+    /// Per-key content
+    pub keys: Vec<KeyLayout>,
+}
+
+/// Content for a single key
+///
+/// Where multiple of `text`, `image` and `colour` are set, text takes precedence
+/// (with `colour` used as the text background), followed by image then colour.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct KeyLayout {
+    /// Key index
+    pub key: u8,
+    /// Key colour (or text background colour)
+    pub colour: Option<Colour>,
+    /// Image file
+    pub image: Option<String>,
+    /// Options for image loading
+    pub image_options: ImageOptions,
+    /// Text, split over lines on '\n'
+    pub text: Option<String>,
+    /// Text colour
+    pub text_colour: Option<Colour>,
+}
+
+impl Layout {
+    /// Apply the layout to the provided device
+    ///
+    /// Key indices are validated before writing, returning `Error::InvalidKeyIndex`
+    /// without modifying the device if any key does not exist.
+    pub fn apply(&self, deck: &mut StreamDeck) -> Result<(), Error> {
+        if let Some(k) = self.keys.iter().find(|k| !deck.is_valid_key(k.key)) {
+            error!("Invalid key index {} for device {:?}", k.key, deck.kind());
+            return Err(Error::InvalidKeyIndex);
+        }
+
+        if let Some(b) = self.brightness {
+            deck.try_set_brightness(b)?;
+        }
+
+        // Load the font only if required
+        let font_data = match (&self.font, self.keys.iter().any(|k| k.text.is_some())) {
+            (Some(f), true) => Some(std::fs::read(f)?),
+            (None, true) => {
+                return Err(Error::Io(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "layout font is required for text keys",
+                )))
+            }
+            _ => None,
+        };
+        let font = match &font_data {
+            Some(d) => Some(
+                FontRef::try_from_slice(d)
+                    .map_err(|e| Error::Io(IoError::new(ErrorKind::InvalidData, e)))?,
+            ),
+            None => None,
+        };
+
+        for k in &self.keys {
+            match (&k.text, &k.image, &k.colour) {
+                (Some(text), _, background) => {
+                    let mut opts = TextOptions::default();
+                    if let Some(c) = background {
+                        opts.background = c.clone();
+                    }
+                    if let Some(c) = &k.text_colour {
+                        opts.foreground = c.clone();
+                    }
+                    if let Some(s) = self.text_scale {
+                        opts.scale = PxScale::from(s);
+                    }
+
+                    let pos = TextPosition::Absolute { x: 0, y: 0 };
+                    deck.set_button_text(k.key, font.as_ref().unwrap(), &pos, text, &opts)?;
+                }
+                (None, Some(file), _) => deck.set_button_file(k.key, file, &k.image_options)?,
+                (None, None, Some(c)) => deck.set_button_rgb(k.key, c)?,
+                (None, None, None) => warn!("No content for key {}", k.key),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::mock_deck;
+    use crate::Kind;
+
+    #[test]
+    fn parse_layout() {
+        let l: Layout = serde_json::from_str(
+            r#"{
+                "brightness": 50,
+                "keys": [
+                    { "key": 0, "colour": { "r": 255, "g": 0, "b": 0 } },
+                    { "key": 1, "image": "icons/power.png", "image_options": { "invert": true } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(l.brightness, Some(50));
+        assert_eq!(l.keys.len(), 2);
+        assert_eq!(l.keys[1].image.as_deref(), Some("icons/power.png"));
+    }
+
+    #[test]
+    fn invalid_layout_keys() {
+        let l: Layout = serde_json::from_str(
+            r#"{
+                "brightness": 50,
+                "keys": [
+                    { "key": 0, "colour": { "r": 255, "g": 0, "b": 0 } },
+                    { "key": 6, "colour": { "r": 255, "g": 0, "b": 0 } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+        assert!(matches!(l.apply(&mut deck), Err(Error::InvalidKeyIndex)));
+        assert!(mock.writes().is_empty() && mock.feature_reports().is_empty());
+    }
+}
@@ -1,4 +1,9 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::hash::{Hash, Hasher};
 use std::io::Error as IoError;
+use std::path::Path;
 use std::time::Duration;
 
 #[macro_use]
@@ -6,28 +11,120 @@ extern crate log;
 
 extern crate hidapi;
 use ab_glyph::{FontRef, PxScale};
-use hidapi::{HidApi, HidDevice, HidError};
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidError};
 
 extern crate image;
-use image::{DynamicImage, ImageBuffer, ImageError, Rgb};
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, ImageError, Pixel, Rgb, RgbImage};
 
 pub mod images;
-use crate::images::{apply_transform, encode_jpeg};
-pub use crate::images::{Colour, ImageOptions};
+use crate::images::{apply_gamma, apply_transform, compose_transform, encode_jpeg};
+pub use crate::images::{Colour, ImageFilter, ImageOptions, ImageOptionsBuilder};
 
 pub mod info;
 pub use info::*;
 
-use imageproc::drawing::draw_text_mut;
+pub mod hotplug;
+pub use hotplug::{watch_devices, DeviceEvent, DeviceWatcher};
+
+pub mod input;
+pub use input::{InputEvent, InputEventsIter, InputManager, InputState, TOUCH_BUTTONS};
+
+#[cfg(feature = "serde")]
+pub mod layout;
+#[cfg(feature = "serde")]
+pub use layout::{KeyLayout, Layout};
+
+pub mod animation;
+pub use animation::{Animation, FrameSink, FrameTarget, GifAnimation, Marquee};
+
+pub mod button;
+pub use button::{Button, ToggleButton};
+
+pub mod pages;
+pub use pages::{KeyCallback, Page, Pages};
+
+pub mod shared;
+pub use shared::SharedStreamDeck;
+
+pub mod transport;
+pub use transport::{MockDevice, Transport};
+
+mod text;
+
+pub mod preview;
+pub use preview::PreviewDeck;
+
+pub mod screensaver;
+pub use screensaver::{IdleAction, Screensaver};
+
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::rect::Rect;
 use std::str::FromStr;
 use thiserror::Error;
 
 /// StreamDeck object
 pub struct StreamDeck {
     kind: Kind,
-    device: HidDevice,
+    device: Box<dyn Transport>,
+
+    // Connection parameters (vid, pid, serial) retained for reconnection,
+    // None for devices using a custom transport
+    connection: Option<(u16, u16, Option<String>)>,
+
+    // HID device path for devices connected by path, used for reconnection
+    // where the device does not report a serial
+    path: Option<CString>,
+
+    // Last brightness set, restored on wake
+    brightness: u8,
+
+    // Current blocking mode
+    blocking: bool,
+
+    // Last button states and queued non-button input reports
+    last_buttons: Vec<u8>,
+    input_queue: VecDeque<([u8; INPUT_REPORT_LEN], usize)>,
+
+    // Scratch buffer for image reports
+    buf: Vec<u8>,
+
+    // Hashes of the last image written to each key, when de-duplication is enabled
+    dedupe: bool,
+    key_hashes: Vec<Option<u64>>,
+
+    // Last image written to each key, when state tracking is enabled
+    track_state: bool,
+    key_images: Vec<Option<DeviceImage>>,
+
+    // Mounting orientation, applied in addition to the device transform
+    rotation: Rotation,
+    mirror: Mirroring,
+
+    // Gamma correction applied to solid colours
+    colour_gamma: Option<f32>,
+
+    // Override for the length of image reports
+    max_packet_len: Option<usize>,
+
+    // Encoded touch button images, for restoring after dial acknowledgement
+    touch_images: Vec<Option<Vec<u8>>>,
+
+    // Timeout for `read_buttons_default` / `read_input_default`
+    default_timeout: Option<Duration>,
+
+    // Custom filter applied when preparing images
+    image_filter: Option<ImageFilter>,
 }
 
+/// Maximum input report length, see [Kind::input_report_len]
+const INPUT_REPORT_LEN: usize = 36;
+
+/// Maximum number of queued input reports, see `StreamDeck::read_buttons`
+const INPUT_QUEUE_LEN: usize = 32;
+
+/// Length of the header on touchscreen image reports
+const LCD_HEADER_LEN: usize = 16;
+
 /// Helper object for filtering device connections
 #[cfg(feature = "structopt")]
 #[derive(structopt::StructOpt)]
@@ -43,6 +140,10 @@ pub struct Filter {
     #[structopt(long, env = "USB_SERIAL")]
     /// USB Device Serial
     pub serial: Option<String>,
+
+    #[structopt(long, env = "USB_PATH")]
+    /// HID device path, overriding VID / PID / serial matching
+    pub path: Option<String>,
 }
 
 fn u16_parse_hex(s: &str) -> Result<u16, std::num::ParseIntError> {
@@ -52,7 +153,7 @@ fn u16_parse_hex(s: &str) -> Result<u16, std::num::ParseIntError> {
 #[derive(Debug, Error)]
 pub enum Error {
     #[error(transparent)]
-    Hid(#[from] HidError),
+    Hid(HidError),
     #[error(transparent)]
     Io(#[from] IoError),
     #[error(transparent)]
@@ -62,23 +163,106 @@ pub enum Error {
     InvalidImageSize,
     #[error("invalid key index")]
     InvalidKeyIndex,
-    #[error("unrecognised pid")]
-    UnrecognisedPID,
+    #[error("invalid brightness ({0}), expected 0 to 100")]
+    InvalidBrightness(u8),
+    #[error("invalid frame rate ({0}), expected a positive value")]
+    InvalidFrameRate(f32),
+    #[error("unrecognised pid ({0:#06x})")]
+    UnrecognisedPID(u16),
+    #[error("invalid firmware version: {0}")]
+    InvalidVersion(String),
     #[error("unsupported input")]
     UnsupportedInput,
     #[error("no data")]
     NoData,
+    #[error("short report ({0} bytes)")]
+    ShortReport(usize),
+    #[error("device disconnected")]
+    Disconnected,
+}
+
+/// Platform error messages indicating the device has been removed,
+/// hidapi reports these as text only so are matched exactly
+const DISCONNECT_MESSAGES: &[&str] = &[
+    // Linux / macOS (ENODEV)
+    "No such device",
+    // Windows (ERROR_DEVICE_NOT_CONNECTED)
+    "The device is not connected",
+    // macOS (kIOReturnNotAttached)
+    "0xE00002D8",
+];
+
+/// Platform error codes indicating the device has been removed
+#[cfg(windows)]
+const DISCONNECT_CODES: &[i32] = &[
+    // ERROR_DEVICE_NOT_CONNECTED
+    1167,
+];
+#[cfg(not(windows))]
+const DISCONNECT_CODES: &[i32] = &[
+    // ENODEV
+    19,
+];
+
+impl From<HidError> for Error {
+    fn from(e: HidError) -> Self {
+        let disconnected = match &e {
+            HidError::HidApiError { message } => {
+                DISCONNECT_MESSAGES.iter().any(|m| message.contains(m))
+            }
+            HidError::IoError { error } => error
+                .raw_os_error()
+                .is_some_and(|c| DISCONNECT_CODES.contains(&c)),
+            _ => false,
+        };
+
+        match disconnected {
+            true => Error::Disconnected,
+            false => Error::Hid(e),
+        }
+    }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct DeviceImage {
     data: Vec<u8>,
 }
 
 impl DeviceImage {
     /// Constructs [DeviceImage] from a byte array
+    ///
+    /// The data is not validated, see `from_bytes_checked`
     pub fn from_bytes(data: Vec<u8>) -> Self {
         Self::from(data)
     }
+
+    /// Constructs [DeviceImage] from a byte array in the device format for `kind`,
+    /// returning `Error::InvalidImageSize` if the data is not plausible
+    ///
+    /// See `StreamDeck::write_raw_image` for the expected format
+    pub fn from_bytes_checked(kind: Kind, data: Vec<u8>) -> Result<Self, Error> {
+        if !is_device_image(kind, &data) {
+            return Err(Error::InvalidImageSize);
+        }
+        Ok(Self::from(data))
+    }
+
+    /// Constructs a black [DeviceImage] for `kind`
+    pub fn blank(kind: Kind) -> Result<Self, Error> {
+        convert_image(kind, vec![0u8; kind.image_size_bytes()])
+    }
+
+    /// Fetch the image data in the device format, as written to the device
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Compute a hash of the image data, used for de-duplicating writes
+    pub(crate) fn hash(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        self.data.hash(&mut h);
+        h.finish()
+    }
 }
 
 impl From<Vec<u8>> for DeviceImage {
@@ -87,6 +271,38 @@ impl From<Vec<u8>> for DeviceImage {
     }
 }
 
+/// Snapshot of the images written to each key, see [StreamDeck::snapshot]
+#[derive(Clone)]
+pub struct DeckSnapshot {
+    images: Vec<Option<DeviceImage>>,
+}
+
+impl DeckSnapshot {
+    /// Create an empty snapshot for the provided device kind
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            images: vec![None; kind.keys() as usize],
+        }
+    }
+
+    /// Fetch the image for a key, `None` if no image was recorded
+    pub fn image(&self, key: u8) -> Option<&DeviceImage> {
+        self.images.get(key as usize).and_then(|i| i.as_ref())
+    }
+
+    /// Set the image for a key
+    pub fn set_image(&mut self, key: u8, image: DeviceImage) -> Result<(), Error> {
+        match self.images.get_mut(key as usize) {
+            Some(i) => *i = Some(image),
+            None => return Err(Error::InvalidKeyIndex),
+        }
+        Ok(())
+    }
+}
+
+/// Elgato USB Vendor Identifier (VID)
+pub const ELGATO_VID: u16 = 0x0fd9;
+
 /// Device USB Product Identifiers (PIDs)
 pub mod pids {
     pub const ORIGINAL: u16 = 0x0060;
@@ -96,6 +312,79 @@ pub mod pids {
     pub const MK2: u16 = 0x0080;
     pub const REVISED_MINI: u16 = 0x0090;
     pub const PLUS: u16 = 0x0084;
+
+    // Later hardware revisions, using the same protocol as existing kinds
+    pub const XL_V2: u16 = 0x008f;
+    pub const MK2_SCISSOR: u16 = 0x00a5;
+    pub const MINI_DISCORD: u16 = 0x00b3;
+}
+
+/// Match a device kind from a USB PID
+fn kind_from_pid(pid: u16) -> Option<Kind> {
+    let kind = match pid {
+        pids::ORIGINAL => Kind::Original,
+        pids::MINI => Kind::Mini,
+
+        pids::ORIGINAL_V2 => Kind::OriginalV2,
+        pids::XL | pids::XL_V2 => Kind::Xl,
+        pids::MK2 | pids::MK2_SCISSOR => Kind::Mk2,
+        pids::REVISED_MINI | pids::MINI_DISCORD => Kind::RevisedMini,
+        pids::PLUS => Kind::Plus,
+
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+/// Information on a discovered (but not connected) device
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ProbedDevice {
+    pub kind: Kind,
+    pub vid: u16,
+    pub pid: u16,
+    /// Device serial, `None` where this is not reported or empty (as with some clones)
+    /// in which case the device can only be identified by `path`
+    pub serial: Option<String>,
+    /// USB device release number (`bcdDevice`), which identifies the hardware / firmware
+    /// revision without opening the device. This is not the firmware version string
+    /// returned by `StreamDeck::version`.
+    pub release: u16,
+    /// Platform specific HID device path, for connecting to a specific physical
+    /// device with `StreamDeck::connect_by_path`. Paths may change when reconnected.
+    pub path: String,
+}
+
+impl ProbedDevice {
+    /// Build a [ProbedDevice] from hidapi device information,
+    /// returning None for non-streamdeck devices
+    pub(crate) fn from_info(info: &DeviceInfo) -> Option<Self> {
+        if info.vendor_id() != ELGATO_VID {
+            return None;
+        }
+
+        Self::try_from_info(info).ok()
+    }
+
+    /// Build a [ProbedDevice] from hidapi device information for any vendor ID,
+    /// returning `Error::UnrecognisedPID` for unknown devices
+    fn try_from_info(info: &DeviceInfo) -> Result<Self, Error> {
+        let pid = info.product_id();
+        let kind = kind_from_pid(pid).ok_or(Error::UnrecognisedPID(pid))?;
+
+        Ok(Self {
+            kind,
+            vid: info.vendor_id(),
+            pid,
+            serial: info
+                .serial_number()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            release: info.release_number(),
+            path: info.path().to_string_lossy().into_owned(),
+        })
+    }
 }
 
 impl StreamDeck {
@@ -114,29 +403,203 @@ impl StreamDeck {
         serial: Option<String>,
     ) -> Result<StreamDeck, Error> {
         // Match info based on PID
-        let kind = match pid {
-            pids::ORIGINAL => Kind::Original,
-            pids::MINI => Kind::Mini,
+        let kind = kind_from_pid(pid).ok_or(Error::UnrecognisedPID(pid))?;
+
+        StreamDeck::connect_assume_with_hid(api, vid, pid, serial, kind)
+    }
 
-            pids::ORIGINAL_V2 => Kind::OriginalV2,
-            pids::XL => Kind::Xl,
-            pids::MK2 => Kind::Mk2,
-            pids::REVISED_MINI => Kind::RevisedMini,
-            pids::PLUS => Kind::Plus,
+    /// Connect to a streamdeck device using the provided device kind, regardless of PID
+    ///
+    /// This allows support for new or unrecognised PIDs to be tested by assuming
+    /// the protocol of an existing kind. Using an incorrect kind may result in
+    /// garbled images or input, please report PIDs that work so they can be added.
+    pub fn connect_assume(
+        vid: u16,
+        pid: u16,
+        serial: Option<String>,
+        kind: Kind,
+    ) -> Result<StreamDeck, Error> {
+        let api = HidApi::new()?;
+        StreamDeck::connect_assume_with_hid(&api, vid, pid, serial, kind)
+    }
 
-            _ => return Err(Error::UnrecognisedPID),
-        };
+    /// Connect to a streamdeck device using the provided device kind with an already
+    /// initialised HidApi instance, see `connect_assume`
+    pub fn connect_assume_with_hid(
+        api: &HidApi,
+        vid: u16,
+        pid: u16,
+        serial: Option<String>,
+        kind: Kind,
+    ) -> Result<StreamDeck, Error> {
+        if kind_from_pid(pid) != Some(kind) {
+            debug!("Assuming kind {:?} for pid {:#06x}", kind, pid);
+        }
 
         debug!("Device info: {:?}", kind);
 
         // Attempt to connect to device
-        let device = match &serial {
-            Some(s) => api.open_serial(vid, pid, s),
-            None => api.open(vid, pid),
-        }?;
+        let device = open_interface(api, vid, pid, serial.as_deref())?;
+
+        // Capture the device serial if not specified, so reconnection
+        // returns to the same physical device
+        let serial = match serial {
+            Some(s) => Some(s),
+            None => device.get_serial_number_string().ok().flatten(),
+        };
+
+        let connection = Some((vid, pid, serial));
+        Ok(StreamDeck::new(kind, Box::new(device), connection))
+    }
+
+    /// Connect to a streamdeck device by HID device path (see [ProbedDevice::path]),
+    /// for devices which do not report a unique serial number
+    pub fn connect_by_path(path: &CStr) -> Result<StreamDeck, Error> {
+        let api = HidApi::new()?;
+        StreamDeck::connect_by_path_with_hid(&api, path)
+    }
+
+    /// Connect to a streamdeck device by HID device path with an already
+    /// initialised HidApi instance
+    ///
+    /// The serial (if reported) is retained for `reconnect`, devices without
+    /// a serial reconnect using the same path.
+    pub fn connect_by_path_with_hid(api: &HidApi, path: &CStr) -> Result<StreamDeck, Error> {
+        let device = api.open_path(path)?;
+        let info = device.get_device_info()?;
+
+        let probed = ProbedDevice::try_from_info(&info)?;
+
+        debug!("Device info: {:?} (path: {:?})", probed.kind, path);
+
+        let connection = Some((probed.vid, probed.pid, probed.serial));
+        let mut deck = StreamDeck::new(probed.kind, Box::new(device), connection);
+        deck.path = Some(path.to_owned());
+
+        Ok(deck)
+    }
+
+    /// Create a streamdeck object using the provided transport
+    ///
+    /// This allows the device protocol to be used over custom transports, or
+    /// exercised without hardware using [MockDevice]. `reconnect` is not supported
+    /// for devices created in this manner.
+    pub fn with_transport(kind: Kind, transport: Box<dyn Transport>) -> StreamDeck {
+        StreamDeck::new(kind, transport, None)
+    }
+
+    /// Create a preview device which records key images in memory rather than
+    /// writing to hardware, for visualising layouts during development
+    pub fn preview(kind: Kind) -> PreviewDeck {
+        PreviewDeck::new(kind, MockDevice::new())
+    }
+
+    /// Create a virtual device with the geometry of the provided kind, for UI development
+    ///
+    /// As with `preview` key images are retained for rendering, however written reports
+    /// are discarded rather than recorded so this is suitable for long-running use.
+    /// Input may be injected with `PreviewDeck::mock`.
+    pub fn virtual_device(kind: Kind) -> PreviewDeck {
+        PreviewDeck::new(kind, MockDevice::discarding())
+    }
+
+    fn new(
+        kind: Kind,
+        device: Box<dyn Transport>,
+        connection: Option<(u16, u16, Option<String>)>,
+    ) -> StreamDeck {
+        StreamDeck {
+            device,
+            kind,
+            connection,
+            path: None,
+            brightness: 100,
+            blocking: true,
+            last_buttons: vec![0u8; kind.keys() as usize],
+            input_queue: VecDeque::new(),
+            buf: vec![],
+            dedupe: false,
+            key_hashes: vec![None; kind.keys() as usize],
+            track_state: false,
+            key_images: vec![None; kind.keys() as usize],
+            rotation: Rotation::Rot0,
+            mirror: Mirroring::None,
+            colour_gamma: None,
+            max_packet_len: None,
+            touch_images: vec![],
+            default_timeout: None,
+            image_filter: None,
+        }
+    }
+
+    /// Connect to a streamdeck device, retrying on failure
+    ///
+    /// Up to `attempts` connections are attempted, starting with the provided
+    /// `delay` between attempts and doubling this after each failure.
+    /// The last error is returned if no attempts succeed.
+    pub fn connect_with_retry(
+        vid: u16,
+        pid: u16,
+        serial: Option<String>,
+        attempts: usize,
+        delay: Duration,
+    ) -> Result<StreamDeck, Error> {
+        let mut delay = delay;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let err = match StreamDeck::connect(vid, pid, serial.clone()) {
+                Ok(d) => return Ok(d),
+                // Unknown devices will never succeed, so don't bother retrying
+                Err(e @ Error::UnrecognisedPID(_)) => return Err(e),
+                Err(e) => e,
+            };
+
+            if attempt >= attempts {
+                return Err(err);
+            }
+
+            debug!(
+                "Connection attempt {} failed ({:?}), retrying in {:?}",
+                attempt, err, delay
+            );
+
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+
+    /// Re-open the device using the stored connection parameters
+    ///
+    /// This is useful for recovering from a device being unplugged and replugged.
+    /// Devices connected by path without a serial are re-opened by the same path,
+    /// which may change when the device is replugged.
+    /// On failure the existing (likely invalid) device handle is retained.
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        // Devices created with a custom transport have no connection parameters
+        let (vid, pid, serial) = match &self.connection {
+            Some(c) => c,
+            None => return Err(Error::UnsupportedInput),
+        };
+
+        let api = HidApi::new()?;
+
+        let device = match (serial, &self.path) {
+            (None, Some(p)) => api.open_path(p)?,
+            _ => open_interface(&api, *vid, *pid, serial.as_deref())?,
+        };
+
+        debug!("Reconnected to device: {:?}", self.kind);
+
+        // Restore blocking mode on the new handle
+        device.set_blocking_mode(self.blocking)?;
 
-        // Return streamdeck object
-        Ok(StreamDeck { device, kind })
+        self.device = Box::new(device);
+        self.clear_key_hashes();
+
+        Ok(())
     }
 
     /// Fetch the connected device kind
@@ -147,6 +610,12 @@ impl StreamDeck {
         self.kind
     }
 
+    /// Check whether a key index is valid for the connected device,
+    /// for validating indices prior to writing
+    pub fn is_valid_key(&self, index: u8) -> bool {
+        self.kind.is_valid_key(index)
+    }
+
     /// Fetch the device manufacturer string
     pub fn manufacturer(&mut self) -> Result<String, Error> {
         let s = self.device.get_manufacturer_string()?;
@@ -160,15 +629,22 @@ impl StreamDeck {
     }
 
     /// Fetch the device serial
+    ///
+    /// Returns `Error::NoData` for devices which do not report a serial
     pub fn serial(&mut self) -> Result<String, Error> {
         let s = self.device.get_serial_number_string()?;
-        Ok(s.unwrap())
+        s.ok_or(Error::NoData)
+    }
+
+    /// Fetch the HID device path, for devices connected with `connect_by_path`
+    pub fn path(&self) -> Option<&CStr> {
+        self.path.as_deref()
     }
 
     /// Fetch the device firmware version
     pub fn version(&mut self) -> Result<String, Error> {
         let mut buff = [0u8; 17];
-        buff[0] = if self.kind.is_v2() { 0x05 } else { 0x04 };
+        buff[0] = self.version_report_id();
 
         let _s = self.device.get_feature_report(&mut buff)?;
 
@@ -176,9 +652,54 @@ impl StreamDeck {
         Ok(std::str::from_utf8(&buff[offset..]).unwrap().to_string())
     }
 
+    /// Fetch and parse the device firmware version, for comparison when
+    /// detecting firmware capabilities
+    ///
+    /// Returns `Error::InvalidVersion` if the version string cannot be parsed,
+    /// see `version` for the raw string.
+    pub fn firmware_version(&mut self) -> Result<FirmwareVersion, Error> {
+        self.version()?.parse().map_err(Error::InvalidVersion)
+    }
+
+    /// Check the device is responding, for detecting devices that have hung
+    /// without a clean disconnect
+    ///
+    /// This reads the firmware version feature report, returning `Error::Disconnected`
+    /// if this fails or no data is returned. Note feature report reads do not have
+    /// a timeout, so on some platforms this may itself block on an unresponsive device.
+    pub fn ping(&mut self) -> Result<(), Error> {
+        let mut buff = [0u8; 17];
+        buff[0] = self.version_report_id();
+
+        match self.device.get_feature_report(&mut buff) {
+            Ok(n) if n > 0 => Ok(()),
+            Ok(_) => Err(Error::Disconnected),
+            Err(e) => {
+                debug!("Ping failed: {:?}", e);
+                Err(Error::Disconnected)
+            }
+        }
+    }
+
+    /// Feature report ID for reading the firmware version
+    fn version_report_id(&self) -> u8 {
+        match self.kind.is_v2() {
+            true => 0x05,
+            false => 0x04,
+        }
+    }
+
     /// Reset the connected device
+    ///
+    /// This sends a zero-padded feature report sized for the device kind
+    /// (see `Kind::feature_report_len`), `0b 63` for V1 devices (17 bytes)
+    /// and `03 02` for V2 devices (32 bytes).
+    ///
+    /// Returns `Error::UnsupportedInput` for devices without a display
     pub fn reset(&mut self) -> Result<(), Error> {
-        let mut cmd = [0u8; 17];
+        self.check_display()?;
+
+        let mut cmd = vec![0u8; self.kind.feature_report_len()];
 
         if self.kind.is_v2() {
             cmd[..2].copy_from_slice(&[0x03, 0x02]);
@@ -187,166 +708,730 @@ impl StreamDeck {
         }
 
         self.device.send_feature_report(&cmd)?;
+        self.clear_key_hashes();
+        self.clear_key_images();
+        self.touch_images.clear();
 
         Ok(())
     }
 
     /// Set the device display brightness (in percent)
+    ///
+    /// Values above 100 are clamped, see `try_set_brightness` to reject these.
+    /// Returns `Error::UnsupportedInput` for devices without a display
     pub fn set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
-        let mut cmd = [0u8; 17];
-
         let brightness = brightness.min(100);
 
-        if self.kind.is_v2() {
-            cmd[..3].copy_from_slice(&[0x03, 0x08, brightness]);
-        } else {
-            cmd[..6].copy_from_slice(&[0x05, 0x55, 0xaa, 0xd1, 0x01, brightness]);
-        }
-
-        self.device.send_feature_report(&cmd)?;
+        self.write_brightness(brightness)?;
+        self.brightness = brightness;
 
         Ok(())
     }
 
-    /// Set blocking mode
+    /// Set the device display brightness (in percent), returning
+    /// `Error::InvalidBrightness` for values above 100
+    pub fn try_set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        if brightness > 100 {
+            return Err(Error::InvalidBrightness(brightness));
+        }
+
+        self.set_brightness(brightness)
+    }
+
+    /// Set the device display brightness from a floating point percentage
     ///
-    /// See: `read_buttons` for discussion of this functionality
-    pub fn set_blocking(&mut self, blocking: bool) -> Result<(), Error> {
-        self.device.set_blocking_mode(blocking)?;
+    /// Values are clamped to 0.0..=100.0 and rounded to the nearest percent
+    pub fn set_brightness_f32(&mut self, percent: f32) -> Result<(), Error> {
+        let percent = match percent.is_nan() {
+            true => 0.0,
+            false => percent.clamp(0.0, 100.0),
+        };
 
-        Ok(())
+        self.set_brightness(percent.round() as u8)
     }
 
-    /// Probe for connected devices. 
-    /// 
-    /// Returns a list of results, 
-    /// each containing the device kind and PID or an error if the PID is unrecognised
-    pub fn probe() -> Result<Vec<Result<(Kind, u16), Error>>, Error> {
-        let api = HidApi::new()?;
-        let mut available_devices = vec![];
-        for device in api.device_list() {
-            if device.vendor_id() == 0x0fd9 {
-                let deck = match device.product_id() {
-                    pids::MK2 => Ok((Kind::Mk2, pids::MK2)),
-                    pids::XL => Ok((Kind::Xl, pids::XL)),
-                    pids::ORIGINAL_V2 => Ok((Kind::OriginalV2, pids::ORIGINAL_V2)),
-                    pids::ORIGINAL => Ok((Kind::Original, pids::ORIGINAL)),
-                    pids::MINI => Ok((Kind::Mini, pids::MINI)),
-                    pids::PLUS => Ok((Kind::Plus, pids::PLUS)),
-                    _ => Err(Error::UnrecognisedPID)
-                };
-                available_devices.push(deck);
-            }
-        }
-        Ok(available_devices)
+    /// Fetch the last brightness set via `set_brightness`
+    pub fn brightness(&self) -> u8 {
+        self.brightness
     }
 
-    /// Fetch button states
+    /// Fade the display brightness from the current value to `target` over `duration`
     ///
-    /// In blocking mode this will wait until a report packet has been received
-    /// (or the specified timeout has elapsed). In non-blocking mode this will return
-    /// immediately with a zero vector if no data is available
-    pub fn read_buttons(&mut self, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
-        let mut cmd = [0u8; 36];
-        let keys = self.kind.keys() as usize;
-        let offset = self.kind.key_data_offset();
-
-        match timeout {
-            Some(t) => self
-                .device
-                .read_timeout(&mut cmd[..keys + offset + 1], t.as_millis() as i32)?,
-            None => self.device.read(&mut cmd[..keys + offset + 1])?,
-        };
-
-        if cmd[0] == 0 {
-            return Err(Error::NoData);
+    /// This blocks for the duration of the fade, writing one brightness step per percent
+    pub fn fade_brightness(&mut self, target: u8, duration: Duration) -> Result<(), Error> {
+        let target = target.min(100);
+        let start = self.brightness;
+
+        let steps = (start as i32 - target as i32).unsigned_abs();
+        if steps == 0 {
+            return self.set_brightness(target);
         }
 
-        if self.kind == Kind::Plus {
-            //If the second byte is not 0, a dial or the touchscreen was used, we don't support that here
-            //This would write to indices which represent buttons and thus create faulty output
-            if cmd[1] != 0 {
-                return Err(Error::UnsupportedInput);
-            }
-        }
+        let delay = duration / steps;
+        for i in 1..=steps {
+            let b = match target > start {
+                true => start + i as u8,
+                false => start - i as u8,
+            };
 
-        let mut out = vec![0u8; keys];
-        match self.kind.key_direction() {
-            KeyDirection::RightToLeft => {
-                for (i, val) in out.iter_mut().enumerate() {
-                    // In right-to-left mode(original Streamdeck) the first key has index 1,
-                    // so we don't add the +1 here.
-                    *val = cmd[offset + self.translate_key_index(i as u8)? as usize];
-                }
-            }
-            KeyDirection::LeftToRight => {
-                out[0..keys].copy_from_slice(&cmd[1 + offset..1 + offset + keys]);
-            }
+            self.set_brightness(b)?;
+            std::thread::sleep(delay);
         }
 
-        Ok(out)
+        Ok(())
     }
 
-    /// Fetch image size for the connected device
-    pub fn image_size(&self) -> (usize, usize) {
-        self.kind.image_size()
+    /// Put the device display to sleep
+    ///
+    /// This blanks the display without forgetting the current brightness,
+    /// see `wake` to restore it
+    pub fn sleep(&mut self) -> Result<(), Error> {
+        self.write_brightness(0)
     }
 
-    /// Convert an image into the device dependent format
-    fn convert_image(&self, image: Vec<u8>) -> Result<DeviceImage, Error> {
-        // Check image dimensions
-        if image.len() != self.kind.image_size_bytes() {
-            return Err(Error::InvalidImageSize);
-        }
-        let image = match self.kind.image_mode() {
-            ImageMode::Bmp => image,
-            ImageMode::Jpeg => {
-                let (w, h) = self.kind.image_size();
-                encode_jpeg(&image, w, h)?
-            }
-        };
-        Ok(DeviceImage { data: image })
+    /// Wake the device display, restoring the last brightness set via `set_brightness`
+    /// (or full brightness if this has not been called)
+    pub fn wake(&mut self) -> Result<(), Error> {
+        self.write_brightness(self.brightness)
     }
 
-    /// Set a button to the provided RGB colour
-    pub fn set_button_rgb(&mut self, key: u8, colour: &Colour) -> Result<(), Error> {
-        let mut image = vec![0u8; self.kind.image_size_bytes()];
-        let colour_order = self.kind.image_colour_order();
-
-        for i in 0..image.len() {
-            match i % 3 {
-                0 => {
-                    image[i] = match colour_order {
-                        ColourOrder::BGR => colour.b,
-                        ColourOrder::RGB => colour.r,
-                    }
-                }
-                1 => image[i] = colour.g,
-                2 => {
-                    image[i] = match colour_order {
-                        ColourOrder::BGR => colour.r,
-                        ColourOrder::RGB => colour.b,
-                    }
-                }
-                _ => unreachable!(),
-            };
+    /// Set a touchscreen button region to the provided image (Stream Deck Plus only)
+    ///
+    /// The touchscreen is divided into [TOUCH_BUTTONS] regions above the dials, with
+    /// taps reported as [InputEvent::TouchButton]. The image is resized to fill
+    /// the region. Returns `Error::UnsupportedInput` for devices without a touchscreen.
+    pub fn set_touch_button_image(&mut self, index: u8, image: DynamicImage) -> Result<(), Error> {
+        let (width, height) = self
+            .kind
+            .touchscreen_size()
+            .ok_or(Error::UnsupportedInput)?;
+        if index >= TOUCH_BUTTONS {
+            return Err(Error::InvalidKeyIndex);
         }
-        self.write_button_image(key, &self.convert_image(image)?)?;
+
+        let w = width / TOUCH_BUTTONS as usize;
+        let image = image.resize_exact(w as u32, height as u32, FilterType::Gaussian);
+        let data = encode_jpeg(&image.into_rgb8().into_vec(), w, height)?;
+
+        self.write_lcd_image(index as usize * w, 0, w, height, &data)?;
+
+        self.touch_images.resize(TOUCH_BUTTONS as usize, None);
+        self.touch_images[index as usize] = Some(data);
 
         Ok(())
     }
 
-    /// Set a button to the provided image
-    pub fn set_button_image(&mut self, key: u8, image: DynamicImage) -> Result<(), Error> {
-        let image = apply_transform(image, self.kind.image_rotation(), self.kind.image_mirror());
-        let mut data = image.into_rgb8().into_vec();
-        if matches!(self.kind.image_colour_order(), ColourOrder::BGR) {
-            rgb_to_bgr(&mut data);
+    /// Briefly flash the touchscreen region above a dial to acknowledge input
+    /// (Stream Deck Plus only)
+    ///
+    /// This blocks for `duration` between painting the region with the provided
+    /// colour and restoring the image last set with `set_touch_button_image`
+    /// (or black if none). Dial indices match the touch button regions above them.
+    pub fn acknowledge_dial(
+        &mut self,
+        index: u8,
+        colour: &Colour,
+        duration: Duration,
+    ) -> Result<(), Error> {
+        let (width, height) = self
+            .kind
+            .touchscreen_size()
+            .ok_or(Error::UnsupportedInput)?;
+        if index >= self.kind.dial_count() {
+            return Err(Error::InvalidKeyIndex);
         }
-        self.write_button_image(key, &self.convert_image(data)?)
-    }
 
-    /// Sets a button to the provided text.
+        let w = width / TOUCH_BUTTONS as usize;
+        let encode = |c: &Colour| {
+            let image = ImageBuffer::from_pixel(w as u32, height as u32, Rgb([c.r, c.g, c.b]));
+            encode_jpeg(image.as_raw(), w, height)
+        };
+
+        self.write_lcd_image(index as usize * w, 0, w, height, &encode(colour)?)?;
+
+        std::thread::sleep(duration);
+
+        let previous = match self.touch_images.get(index as usize) {
+            Some(Some(data)) => data.clone(),
+            _ => encode(&Colour { r: 0, g: 0, b: 0 })?,
+        };
+        self.write_lcd_image(index as usize * w, 0, w, height, &previous)?;
+
+        Ok(())
+    }
+
+    /// Write an encoded image to a region of the touchscreen, returning the bytes written
+    fn write_lcd_image(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        image: &[u8],
+    ) -> Result<usize, Error> {
+        // Reuse the scratch buffer, as for key images
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.clear();
+        buf.resize(self.image_packet_len(), 0);
+
+        let region = [x as u16, y as u16, width as u16, height as u16];
+        let res = self.write_lcd_reports(&mut buf, region, image);
+
+        self.buf = buf;
+
+        res
+    }
+
+    /// Writes touchscreen image reports for a region (x, y, width, height)
+    /// using the provided buffer
+    fn write_lcd_reports(
+        &mut self,
+        buf: &mut [u8],
+        region: [u16; 4],
+        image: &[u8],
+    ) -> Result<usize, Error> {
+        const HEADER_LEN: usize = LCD_HEADER_LEN;
+
+        let maxdatalen = buf.len() - HEADER_LEN;
+        let mut page: u16 = 0;
+        let mut offset = 0;
+        let mut written = 0;
+
+        while offset < image.len() {
+            let take = (image.len() - offset).min(maxdatalen);
+            let is_last = offset + take == image.len();
+
+            buf.iter_mut().for_each(|b| *b = 0);
+            buf[0] = 0x02;
+            buf[1] = 0x0c;
+            for (i, v) in region.iter().enumerate() {
+                buf[2 + i * 2..4 + i * 2].copy_from_slice(&v.to_le_bytes());
+            }
+            buf[10] = is_last as u8;
+            buf[11..13].copy_from_slice(&page.to_le_bytes());
+            buf[13..15].copy_from_slice(&(take as u16).to_le_bytes());
+            buf[HEADER_LEN..HEADER_LEN + take].copy_from_slice(&image[offset..offset + take]);
+
+            written += self.device.write(buf)?;
+
+            page += 1;
+            offset += take;
+        }
+
+        Ok(written)
+    }
+
+    /// Set the persistent logo (boot / standby) image
+    ///
+    /// Some V2 devices display a stored image before software connects, however the
+    /// command for updating this is not publicly documented and differs between
+    /// firmware revisions. Until this is confirmed against hardware this returns
+    /// `Error::UnsupportedInput` for all device kinds.
+    pub fn set_logo_image(&mut self, _image: DynamicImage) -> Result<(), Error> {
+        self.check_display()?;
+
+        Err(Error::UnsupportedInput)
+    }
+
+    /// Check the connected device has a display, returning `Error::UnsupportedInput` if not
+    fn check_display(&self) -> Result<(), Error> {
+        match self.kind.has_display() {
+            true => Ok(()),
+            false => Err(Error::UnsupportedInput),
+        }
+    }
+
+    /// Set the device standby timeout (in seconds)
+    ///
+    /// After this period of inactivity the device firmware blanks the display,
+    /// `None` disables the standby timeout. This is only enabled for the MK.2, XL
+    /// and Plus, returning `Error::UnsupportedInput` for other kinds as support
+    /// has not been confirmed.
+    pub fn set_standby_timeout(&mut self, seconds: Option<u32>) -> Result<(), Error> {
+        match self.kind {
+            Kind::Mk2 | Kind::Xl | Kind::Plus => (),
+            _ => return Err(Error::UnsupportedInput),
+        }
+
+        // Zero timeout disables standby
+        let seconds = seconds.unwrap_or(0);
+
+        // Layout follows the "set sleep timeout" feature report in Elgato's HID
+        // protocol documentation for these models: 0x03 0x0d <seconds: u32 LE>
+
+        let mut cmd = [0u8; 17];
+        cmd[..2].copy_from_slice(&[0x03, 0x0d]);
+        cmd[2..6].copy_from_slice(&seconds.to_le_bytes());
+
+        self.device.send_feature_report(&cmd)?;
+
+        Ok(())
+    }
+
+    /// Write the brightness feature report to the device
+    fn write_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        self.check_display()?;
+
+        let mut cmd = [0u8; 17];
+
+        if self.kind.is_v2() {
+            cmd[..3].copy_from_slice(&[0x03, 0x08, brightness]);
+        } else {
+            cmd[..6].copy_from_slice(&[0x05, 0x55, 0xaa, 0xd1, 0x01, brightness]);
+        }
+
+        self.device.send_feature_report(&cmd)?;
+
+        Ok(())
+    }
+
+    /// Send a raw feature report to the device
+    ///
+    /// The first byte of `data` is the report ID. This is a low-level escape hatch
+    /// for commands not otherwise supported, the report format is device specific
+    /// and this API may change.
+    pub fn send_feature_report(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.device.send_feature_report(data)?;
+        Ok(())
+    }
+
+    /// Fetch a raw feature report from the device
+    ///
+    /// Returns up to `len` bytes of the report with the provided ID (including the
+    /// report ID as the first byte). This is a low-level escape hatch for commands
+    /// not otherwise supported, the report format is device specific and this API
+    /// may change.
+    pub fn get_feature_report(&mut self, report_id: u8, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buff = vec![0u8; len.max(1)];
+        buff[0] = report_id;
+
+        let n = self.device.get_feature_report(&mut buff)?;
+        buff.truncate(n);
+
+        Ok(buff)
+    }
+
+    /// Set blocking mode
+    ///
+    /// See: `read_buttons` for discussion of this functionality
+    pub fn set_blocking(&mut self, blocking: bool) -> Result<(), Error> {
+        self.device.set_blocking_mode(blocking)?;
+        self.blocking = blocking;
+
+        Ok(())
+    }
+
+    /// Fetch the current blocking mode (devices default to blocking)
+    pub fn is_blocking(&self) -> bool {
+        self.blocking
+    }
+
+    /// Set the default read timeout, used by `read_buttons_default` and
+    /// `read_input_default` (defaults to `None`, waiting indefinitely)
+    pub fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    /// Fetch the default read timeout, see `set_default_timeout`
+    pub fn default_timeout(&self) -> Option<Duration> {
+        self.default_timeout
+    }
+
+    /// Run the provided closure with the specified blocking mode,
+    /// restoring the prior mode afterwards
+    ///
+    /// The prior mode is restored when the closure returns or panics. Returns an
+    /// error only if the mode could not be set, failures restoring the mode are
+    /// logged so the closure result is not lost.
+    pub fn with_blocking<T, F>(&mut self, blocking: bool, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let prior = self.blocking;
+        self.set_blocking(blocking)?;
+
+        let guard = BlockingGuard { deck: self, prior };
+        Ok(f(guard.deck))
+    }
+
+    /// Probe for connected devices. 
+    /// 
+    /// Returns a list of results, 
+    /// each containing the device kind and PID or an error containing the PID if this is unrecognised
+    #[allow(clippy::type_complexity)]
+    pub fn probe() -> Result<Vec<Result<(Kind, u16), Error>>, Error> {
+        StreamDeck::probe_with_vid(ELGATO_VID)
+    }
+
+    /// Probe for connected devices with the provided vendor ID,
+    /// for OEM variants or virtual devices that do not use [ELGATO_VID]
+    ///
+    /// See [StreamDeck::probe] for details
+    #[allow(clippy::type_complexity)]
+    pub fn probe_with_vid(vid: u16) -> Result<Vec<Result<(Kind, u16), Error>>, Error> {
+        let devices = StreamDeck::probe_devices(vid)?;
+        Ok(devices
+            .into_iter()
+            .map(|r| r.map(|d| (d.kind, d.pid)))
+            .collect())
+    }
+
+    /// Probe for connected devices with the provided vendor ID, returning device
+    /// information (including serial numbers) from the device list
+    ///
+    /// Devices are not opened, so this does not require permission to access each device.
+    /// See [StreamDeck::probe] for details.
+    #[allow(clippy::type_complexity)]
+    pub fn probe_devices(vid: u16) -> Result<Vec<Result<ProbedDevice, Error>>, Error> {
+        let api = HidApi::new()?;
+
+        // Devices may expose multiple interfaces, so the preferred interface
+        // is listed first and further interfaces with the same serial are skipped.
+        // Devices without serials cannot be distinguished, so are not de-duplicated.
+        let mut infos: Vec<_> = api.device_list().filter(|d| d.vendor_id() == vid).collect();
+        infos.sort_by_key(|d| interface_rank(d));
+
+        let mut seen = vec![];
+        let mut devices = vec![];
+        for info in infos {
+            let device = ProbedDevice::try_from_info(info);
+
+            if let Ok(ProbedDevice {
+                pid,
+                serial: Some(s),
+                ..
+            }) = &device
+            {
+                let id = (*pid, s.clone());
+                if seen.contains(&id) {
+                    continue;
+                }
+                seen.push(id);
+            }
+            devices.push(device);
+        }
+
+        Ok(devices)
+    }
+
+    /// Fetch button states
+    ///
+    /// In blocking mode this will wait until a report packet has been received
+    /// (or the specified timeout has elapsed). In non-blocking mode this will return
+    /// immediately with a zero vector if no data is available
+    ///
+    /// On the Stream Deck Plus, dial and touchscreen reports return the last known
+    /// button states, with the reports queued for [InputManager]
+    pub fn read_buttons(&mut self, timeout: Option<Duration>) -> Result<Vec<u8>, Error> {
+        // Read full reports, so dial / touch data on the Plus may be queued
+        let (cmd, n) = self.read_report(timeout, self.kind.input_report_len())?;
+
+        //If the second byte is not 0, a dial or the touchscreen was used
+        //This would write to indices which represent buttons and thus create faulty output
+        if self.kind == Kind::Plus && n >= 2 && cmd[1] != 0 {
+            if self.input_queue.len() >= INPUT_QUEUE_LEN {
+                self.input_queue.pop_front();
+            }
+            self.input_queue.push_back((cmd, n));
+
+            return Ok(self.last_buttons.clone());
+        }
+
+        let buttons = self.decode_buttons(&cmd[..n])?;
+        self.last_buttons.clone_from(&buttons);
+
+        Ok(buttons)
+    }
+
+    /// Fetch button states using the default timeout, see `set_default_timeout`
+    pub fn read_buttons_default(&mut self) -> Result<Vec<u8>, Error> {
+        self.read_buttons(self.default_timeout)
+    }
+
+    /// Poll for button states without blocking
+    ///
+    /// Returns `Ok(None)` if no report was available, errors are reserved for
+    /// actual failures
+    pub fn poll_buttons(&mut self) -> Result<Option<Vec<bool>>, Error> {
+        match self.read_buttons(Some(Duration::ZERO)) {
+            Ok(b) => Ok(Some(b.iter().map(|v| *v != 0).collect())),
+            Err(Error::NoData) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read a raw input report
+    ///
+    /// See `read_buttons` for discussion of blocking and timeouts, returns
+    /// `Error::NoData` if no report was available. For decoded input
+    /// (including dials and touchscreens) see [InputManager].
+    ///
+    /// Reports queued by `read_buttons` are returned before reading from the device.
+    /// Bytes beyond those received from the device are zeroed, see `read_input_len`
+    /// to also fetch the received length.
+    pub fn read_input(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<[u8; INPUT_REPORT_LEN], Error> {
+        self.read_input_len(timeout).map(|(cmd, _n)| cmd)
+    }
+
+    /// Read a raw input report using the default timeout, see `set_default_timeout`
+    pub fn read_input_default(&mut self) -> Result<[u8; INPUT_REPORT_LEN], Error> {
+        self.read_input(self.default_timeout)
+    }
+
+    /// Read a raw input report, returning the report and the number of bytes received
+    pub fn read_input_len(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<([u8; INPUT_REPORT_LEN], usize), Error> {
+        if let Some(r) = self.input_queue.pop_front() {
+            return Ok(r);
+        }
+
+        self.read_report(timeout, self.kind.input_report_len())
+    }
+
+    /// Read up to `len` bytes of an input report from the device,
+    /// returning the zero-padded report and the number of bytes received
+    fn read_report(
+        &mut self,
+        timeout: Option<Duration>,
+        len: usize,
+    ) -> Result<([u8; INPUT_REPORT_LEN], usize), Error> {
+        let mut cmd = [0u8; INPUT_REPORT_LEN];
+
+        let n = match timeout {
+            Some(t) => self
+                .device
+                .read_timeout(&mut cmd[..len], t.as_millis() as i32)?,
+            None => self.device.read(&mut cmd[..len])?,
+        };
+
+        if n == 0 || cmd[0] == 0 {
+            return Err(Error::NoData);
+        }
+
+        Ok((cmd, n))
+    }
+
+    /// Decode button states from a button input report
+    ///
+    /// Returns `Error::ShortReport` if the report is too short to contain all keys
+    pub(crate) fn decode_buttons(&self, cmd: &[u8]) -> Result<Vec<u8>, Error> {
+        let keys = self.kind.keys() as usize;
+        let offset = self.kind.key_data_offset();
+        let index_offset = self.kind.key_index_offset() as usize;
+
+        if cmd.len() < 1 + offset + index_offset + keys {
+            return Err(Error::ShortReport(cmd.len()));
+        }
+
+        let mut out = vec![0u8; keys];
+        match self.kind.key_direction() {
+            KeyDirection::RightToLeft => {
+                for (i, val) in out.iter_mut().enumerate() {
+                    // In right-to-left mode(original Streamdeck) the first key has index 1,
+                    // so we don't add the +1 here.
+                    *val = cmd[offset + translate_key_index(self.kind, i as u8)? as usize];
+                }
+            }
+            KeyDirection::LeftToRight => {
+                // Apply the same index offset as `translate_key_index`, so reads
+                // and writes share the same key indices
+                let start = 1 + offset + index_offset;
+                out[0..keys].copy_from_slice(&cmd[start..start + keys]);
+            }
+        }
+
+        // Keys are reversed when the device is mounted upside down
+        if self.rotation == Rotation::Rot180 {
+            out.reverse();
+        }
+
+        Ok(out)
+    }
+
+    /// Fetch image size for the connected device
+    pub fn image_size(&self) -> (usize, usize) {
+        self.kind.image_size()
+    }
+
+    /// Convert an image into the device dependent format
+    fn convert_image(&self, image: Vec<u8>) -> Result<DeviceImage, Error> {
+        convert_image(self.kind, image)
+    }
+
+    /// Set a button to the provided RGB colour
+    pub fn set_button_rgb(&mut self, key: u8, colour: &Colour) -> Result<(), Error> {
+        let image = self.prepare_colour(colour)?;
+        self.write_button_image(key, &image)?;
+
+        Ok(())
+    }
+
+    /// Set all buttons to the provided RGB colour
+    pub fn fill_all(&mut self, colour: &Colour) -> Result<(), Error> {
+        let image = self.prepare_colour(colour)?;
+        for key in 0..self.kind.keys() {
+            self.write_button_image(key, &image)?;
+        }
+        Ok(())
+    }
+
+    /// Clear all buttons (set to black)
+    pub fn clear_all(&mut self) -> Result<(), Error> {
+        self.fill_all(&Colour { r: 0, g: 0, b: 0 })
+    }
+
+    /// Prepare a solid colour image for writing to the device
+    ///
+    /// Gamma correction is applied if set with `set_colour_gamma`
+    pub fn prepare_colour(&self, colour: &Colour) -> Result<DeviceImage, Error> {
+        let colour = match self.colour_gamma {
+            Some(g) => {
+                let mut c = [colour.r, colour.g, colour.b];
+                apply_gamma(&mut c, g);
+                Colour {
+                    r: c[0],
+                    g: c[1],
+                    b: c[2],
+                }
+            }
+            None => colour.clone(),
+        };
+
+        self.convert_image(self.kind.colour_image(&colour))
+    }
+
+    /// Set gamma correction applied to solid colours (ie. `set_button_rgb` and `fill_all`),
+    /// using the same curve as [ImageOptions::with_gamma] for consistency with images
+    pub fn set_colour_gamma(&mut self, gamma: Option<f32>) {
+        self.colour_gamma = gamma;
+    }
+
+    /// Pulse a button between black and the provided colour
+    ///
+    /// This blocks for `cycles` periods, with frames prepared up-front
+    /// and the button left black on completion
+    pub fn pulse_button(
+        &mut self,
+        key: u8,
+        colour: &Colour,
+        period: Duration,
+        cycles: u32,
+    ) -> Result<(), Error> {
+        const STEPS: u32 = 8;
+
+        // Prepare each brightness level from black to full colour
+        let mut frames = vec![];
+        for i in 0..=STEPS {
+            let scale = |c: u8| (c as u32 * i / STEPS) as u8;
+            let c = Colour {
+                r: scale(colour.r),
+                g: scale(colour.g),
+                b: scale(colour.b),
+            };
+            frames.push(self.prepare_colour(&c)?);
+        }
+
+        let delay = period / (STEPS * 2);
+
+        for _ in 0..cycles {
+            // Fade up then back down
+            let up = 0..STEPS as usize;
+            let down = (1..=STEPS as usize).rev();
+
+            for i in up.chain(down) {
+                self.write_button_image(key, &frames[i])?;
+                std::thread::sleep(delay);
+            }
+        }
+
+        self.write_button_image(key, &frames[0])?;
+        Ok(())
+    }
+
+    /// Set a button to the provided image
+    pub fn set_button_image(&mut self, key: u8, image: DynamicImage) -> Result<(), Error> {
+        let (rotation, mirror) = self.image_transform();
+        let image = apply_transform(image, rotation, mirror);
+        let mut data = image.into_rgb8().into_vec();
+        if matches!(self.kind.image_colour_order(), ColourOrder::Bgr) {
+            rgb_to_bgr(&mut data);
+        }
+        self.write_button_image(key, &self.convert_image(data)?)?;
+        Ok(())
+    }
+
+    /// Set a button to the provided image, optionally inverting colours
+    ///
+    /// This avoids reloading an image to toggle inversion (ie. for press feedback).
+    /// Where an image is toggled frequently it may be cheaper to prepare both
+    /// states once with `prepare_image` and switch with `write_button_image`.
+    pub fn set_button_image_inverted(
+        &mut self,
+        key: u8,
+        image: &DynamicImage,
+        invert: bool,
+    ) -> Result<(), Error> {
+        let mut image = image.clone();
+        if invert {
+            image.invert();
+        }
+        self.set_button_image(key, image)
+    }
+
+    /// Cross-fade a button from one image to another
+    ///
+    /// This blocks while writing `steps` intermediate frames evenly over the
+    /// provided duration, finishing on the `to` image.
+    pub fn transition(
+        &mut self,
+        key: u8,
+        from: &DynamicImage,
+        to: &DynamicImage,
+        duration: Duration,
+        steps: u32,
+    ) -> Result<(), Error> {
+        let (w, h) = self.kind.image_size();
+        let opts = ImageOptions::default();
+
+        // Resize both images once up-front so frames can be blended directly
+        let from = from
+            .resize_exact(w as u32, h as u32, FilterType::Gaussian)
+            .into_rgb8();
+        let to = to
+            .resize_exact(w as u32, h as u32, FilterType::Gaussian)
+            .into_rgb8();
+
+        let delay = duration / (steps + 1);
+
+        for i in 1..=steps {
+            let t = i as f32 / (steps + 1) as f32;
+
+            let mut frame = from.clone();
+            for (p, q) in frame.pixels_mut().zip(to.pixels()) {
+                for c in 0..3 {
+                    let a = p.0[c] as f32;
+                    let b = q.0[c] as f32;
+                    p.0[c] = (a + (b - a) * t).round() as u8;
+                }
+            }
+
+            let image = self.prepare_image(DynamicImage::ImageRgb8(frame), &opts)?;
+            self.write_button_image(key, &image)?;
+
+            std::thread::sleep(delay);
+        }
+
+        let image = self.prepare_image(DynamicImage::ImageRgb8(to), &opts)?;
+        self.write_button_image(key, &image)?;
+        Ok(())
+    }
+
+    /// Sets a button to the provided text.
     /// Will break text over \n linebreaks
     pub fn set_button_text(
         &mut self,
@@ -355,79 +1440,658 @@ impl StreamDeck {
         pos: &TextPosition,
         text: &str,
         opts: &TextOptions,
+    ) -> Result<(), Error> {
+        self.set_button_text_with_fonts(key, std::slice::from_ref(font), pos, text, opts)
+    }
+
+    /// Sets a button to the provided text, with per-glyph font fallback.
+    ///
+    /// Each character is rendered with the first of `fonts` containing a glyph
+    /// for it, allowing symbols or other scripts to be mixed with text.
+    /// Will break text over \n linebreaks
+    pub fn set_button_text_with_fonts(
+        &mut self,
+        key: u8,
+        fonts: &[FontRef],
+        pos: &TextPosition,
+        text: &str,
+        opts: &TextOptions,
+    ) -> Result<(), Error> {
+        self.draw_button_text(key, None, fonts, pos, text, opts)
+    }
+
+    /// Sets a button to the provided text, drawn over a background image
+    ///
+    /// The background is resized to the key, with any transparent regions filled
+    /// with `opts.background`. Where no background is provided this is equivalent
+    /// to `set_button_text`.
+    pub fn set_button_text_with_background(
+        &mut self,
+        key: u8,
+        font: &FontRef,
+        background: Option<&DynamicImage>,
+        pos: &TextPosition,
+        text: &str,
+        opts: &TextOptions,
+    ) -> Result<(), Error> {
+        let fonts = std::slice::from_ref(font);
+        self.draw_button_text(key, background, fonts, pos, text, opts)
+    }
+
+    /// Render text over an optional background image and write this to a button
+    fn draw_button_text(
+        &mut self,
+        key: u8,
+        background: Option<&DynamicImage>,
+        fonts: &[FontRef],
+        pos: &TextPosition,
+        text: &str,
+        opts: &TextOptions,
+    ) -> Result<(), Error> {
+        if fonts.is_empty() {
+            return Err(Error::Io(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "no fonts provided",
+            )));
+        }
+
+        let (width, height) = self.kind.image_size();
+        let fill = Rgb::from(&opts.background);
+        let colour = Rgb::from(&opts.foreground);
+
+        let mut image = match background {
+            Some(b) => {
+                let b = b
+                    .resize_exact(width as u32, height as u32, FilterType::Gaussian)
+                    .into_rgba8();
+                let mut image =
+                    ImageBuffer::from_pixel(width as u32, height as u32, fill.to_rgba());
+                image::imageops::overlay(&mut image, &b, 0, 0);
+                DynamicImage::ImageRgba8(image).into_rgb8()
+            }
+            None => ImageBuffer::from_pixel(width as u32, height as u32, fill),
+        };
+
+        let lines: Vec<_> = text.split('\n').collect();
+        let line_offsets = opts.line_offsets(&lines);
+
+        match pos {
+            TextPosition::Absolute { x, y } => {
+                for (txt, dy) in lines.iter().zip(line_offsets) {
+                    text::draw_text(&mut image, colour, *x, y + dy, opts.scale, fonts, txt);
+                }
+            }
+            TextPosition::Anchored { anchor, offset } => {
+                let area = (width as u32, height as u32);
+
+                // Text box height spans all lines, with the last at the text scale
+                let last = line_offsets.last().copied().unwrap_or(0);
+                let text_height = last + opts.scale.y.round() as i32;
+                let (_, y) = anchor.origin(area, (0, text_height.max(0) as u32));
+
+                for (txt, dy) in lines.iter().zip(line_offsets) {
+                    let (w, _) = text::text_size(opts.scale, fonts, txt);
+                    let (x, _) = anchor.origin(area, (w, 0));
+
+                    let (x, y) = (x + offset.0, y + offset.1 + dy);
+                    text::draw_text(&mut image, colour, x, y, opts.scale, fonts, txt);
+                }
+            }
+        }
+
+        self.set_button_image(key, DynamicImage::ImageRgb8(image))
+    }
+
+    /// Set a button to an icon with a single line label below it
+    ///
+    /// The icon is resized (preserving aspect ratio) to fit above a strip sized
+    /// for the label, centered and composited over the background. The label is
+    /// centered in the strip using the provided text options.
+    pub fn set_button_icon_label(
+        &mut self,
+        key: u8,
+        icon: DynamicImage,
+        label: &str,
+        font: &FontRef,
+        opts: &TextOptions,
     ) -> Result<(), Error> {
         let (width, height) = self.kind.image_size();
-        let background = Rgb([opts.background.r, opts.background.g, opts.background.b]);
-        let colour = Rgb([opts.foreground.r, opts.foreground.g, opts.foreground.b]);
-        let mut image = ImageBuffer::from_pixel(width as u32, height as u32, background);
+        let (width, height) = (width as u32, height as u32);
+        let background = Rgb::from(&opts.background).to_rgba();
+        let mut image = ImageBuffer::from_pixel(width, height, background);
+
+        // Reserve a strip at the bottom for the label, the icon fills the rest
+        let strip = ((opts.scale.y * opts.line_height).ceil() as u32).min(height);
+        let icon_height = height - strip;
+
+        if icon_height > 0 {
+            let icon = icon
+                .resize(width, icon_height, FilterType::Gaussian)
+                .into_rgba8();
+            let x = (width - icon.width()) / 2;
+            let y = (icon_height - icon.height()) / 2;
+
+            image::imageops::overlay(&mut image, &icon, x as i64, y as i64);
+        }
+        let mut image = DynamicImage::ImageRgba8(image).into_rgb8();
+
+        let (text_width, _) = text_size(opts.scale, font, label);
+        let x = (width as i32 - text_width as i32) / 2;
+        let text_height = opts.scale.y.round().min(strip as f32) as u32;
+        let y = (icon_height + (strip - text_height) / 2) as i32;
+
+        let colour = (&opts.foreground).into();
+        draw_text_mut(&mut image, colour, x, y, opts.scale, font, label);
+
+        self.set_button_image(key, DynamicImage::ImageRgb8(image))
+    }
+
+    /// Set a button to a filled rectangle with the provided position and size
+    pub fn set_button_rect(
+        &mut self,
+        key: u8,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        opts: &ShapeOptions,
+    ) -> Result<(), Error> {
+        let (w, h) = self.kind.image_size();
+        let mut image = ImageBuffer::from_pixel(w as u32, h as u32, (&opts.background).into());
+
+        // Draw the border as an outer rectangle, then the fill inset within it
+        let (fill_x, fill_y, fill_w, fill_h) = match &opts.border {
+            Some((c, bw)) => {
+                if width > 0 && height > 0 {
+                    let r = Rect::at(x, y).of_size(width, height);
+                    draw_filled_rect_mut(&mut image, r, c.into());
+                }
+                let bw = *bw;
+                (
+                    x + bw as i32,
+                    y + bw as i32,
+                    width.saturating_sub(bw * 2),
+                    height.saturating_sub(bw * 2),
+                )
+            }
+            None => (x, y, width, height),
+        };
+
+        if fill_w > 0 && fill_h > 0 {
+            let r = Rect::at(fill_x, fill_y).of_size(fill_w, fill_h);
+            draw_filled_rect_mut(&mut image, r, (&opts.fill).into());
+        }
+
+        self.set_button_image(key, DynamicImage::ImageRgb8(image))
+    }
+
+    /// Set a button to a filled circle with the provided center and radius
+    pub fn set_button_circle(
+        &mut self,
+        key: u8,
+        x: i32,
+        y: i32,
+        radius: u32,
+        opts: &ShapeOptions,
+    ) -> Result<(), Error> {
+        let (w, h) = self.kind.image_size();
+        let mut image = ImageBuffer::from_pixel(w as u32, h as u32, (&opts.background).into());
+
+        // Draw the border as an outer circle, then the fill inset within it
+        let fill_radius = match &opts.border {
+            Some((c, bw)) => {
+                draw_filled_circle_mut(&mut image, (x, y), radius as i32, c.into());
+                radius as i32 - *bw as i32
+            }
+            None => radius as i32,
+        };
+
+        if fill_radius >= 0 {
+            draw_filled_circle_mut(&mut image, (x, y), fill_radius, (&opts.fill).into());
+        }
+
+        self.set_button_image(key, DynamicImage::ImageRgb8(image))
+    }
+
+    ///  Set a button to the provided image file
+    pub fn set_button_file<P: AsRef<Path>>(
+        &mut self,
+        key: u8,
+        image: P,
+        opts: &ImageOptions,
+    ) -> Result<(), Error> {
+        self.write_button_image(key, &self.load_image(image, opts)?)?;
+        Ok(())
+    }
+
+    /// Load an image file into the device specific representation
+    ///
+    /// Supported formats are those enabled in the `image` crate, by default this
+    /// includes PNG, JPEG, GIF, BMP, ICO, TIFF and WebP. The `webp` feature enables
+    /// WebP decoding explicitly, for use where `image` default features are disabled.
+    /// Unsupported formats return `Error::Image`.
+    pub fn load_image<P: AsRef<Path>>(
+        &self,
+        image: P,
+        opts: &ImageOptions,
+    ) -> Result<DeviceImage, Error> {
+        let (x, y) = self.kind.image_size();
+
+        let image = images::load_image(
+            image,
+            x,
+            y,
+            self.image_transform(),
+            opts,
+            self.image_filter.as_ref(),
+            self.kind.image_colour_order(),
+        )?;
+        self.convert_image(image)
+    }
+
+    /// Prepare an image for writing to the device
+    ///
+    /// This resizes the image to the device key size and applies the provided options,
+    /// returning a device specific representation for use with `write_button_image`
+    pub fn prepare_image(
+        &self,
+        image: DynamicImage,
+        opts: &ImageOptions,
+    ) -> Result<DeviceImage, Error> {
+        let filter = self.image_filter.as_ref();
+        prepare_image(self.kind, self.image_transform(), filter, image, opts)
+    }
+
+    /// Prepare pixel data for an image without encoding, for inspection or compositing
+    ///
+    /// This applies the same processing as `prepare_image`, returning the resized and
+    /// transformed image in the device colour order and orientation (see
+    /// `write_button_image_raw`) prior to encoding into the device format.
+    pub fn prepare_pixels(
+        &self,
+        image: DynamicImage,
+        opts: &ImageOptions,
+    ) -> Result<RgbImage, Error> {
+        let (x, y) = self.kind.image_size();
+        let filter = self.image_filter.as_ref();
+        let pixels = prepare_pixels(self.kind, self.image_transform(), filter, image, opts)?;
+
+        RgbImage::from_raw(x as u32, y as u32, pixels).ok_or(Error::InvalidImageSize)
+    }
+
+    /// Prepare a batch of images for writing to the device
+    ///
+    /// With the `rayon` feature enabled images are prepared in parallel,
+    /// otherwise sequentially. See `prepare_image` for details.
+    pub fn prepare_images(
+        &self,
+        images: Vec<(u8, DynamicImage)>,
+        opts: &ImageOptions,
+    ) -> Result<Vec<(u8, DeviceImage)>, Error> {
+        let kind = self.kind;
+        let transform = self.image_transform();
+        let filter = self.image_filter.as_ref();
+        let prepare =
+            |(key, image)| prepare_image(kind, transform, filter, image, opts).map(|i| (key, i));
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            images.into_par_iter().map(prepare).collect()
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            images.into_iter().map(prepare).collect()
+        }
+    }
+
+    /// Split an image across all keys as a mosaic
+    ///
+    /// The image is resized to the canvas spanning all keys (ignoring aspect ratio,
+    /// see `Kind::canvas_size`), then the region under each key is written to the key.
+    /// Regions in the gaps between keys are not displayed, see `Kind::key_rect`.
+    pub fn set_mosaic(&mut self, image: DynamicImage, opts: &ImageOptions) -> Result<(), Error> {
+        for (key, image) in self.prepare_mosaic(image, opts)? {
+            self.write_button_image(key, &image)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prepare the per-key images for a mosaic without writing them, see `set_mosaic`
+    pub fn prepare_mosaic(
+        &self,
+        image: DynamicImage,
+        opts: &ImageOptions,
+    ) -> Result<Vec<(u8, DeviceImage)>, Error> {
+        let (w, h) = self.kind.canvas_size();
+        let image = image.resize_exact(w as u32, h as u32, FilterType::Gaussian);
+
+        let mut tiles = vec![];
+        for key in 0..self.kind.keys() {
+            let (x, y, w, h) = self.kind.key_rect(key).ok_or(Error::InvalidKeyIndex)?;
+            let tile = image.crop_imm(x as u32, y as u32, w as u32, h as u32);
+            tiles.push((key, tile));
+        }
+
+        self.prepare_images(tiles, opts)
+    }
+
+    /// Load an animated GIF and start playing it on the provided key
+    ///
+    /// Frames are decoded and converted up-front, with the first frame written
+    /// immediately. `GifAnimation::tick` must then be called periodically to
+    /// display subsequent frames.
+    pub fn play_gif<P: AsRef<Path>>(
+        &mut self,
+        key: u8,
+        path: P,
+        opts: &ImageOptions,
+    ) -> Result<GifAnimation, Error> {
+        let mut animation = GifAnimation::load(self, key, path, opts)?;
+
+        animation.tick(self)?;
+
+        Ok(animation)
+    }
+
+    /// Fetch a handle to the button at the provided key index
+    ///
+    /// Returns `Error::InvalidKeyIndex` if the key does not exist on this device
+    pub fn button(&mut self, key: u8) -> Result<Button<'_>, Error> {
+        Button::new(self, key)
+    }
+
+    /// Convert a zero-indexed (row, column) position to a linear key index
+    pub fn key_index(&self, row: u8, col: u8) -> Result<u8, Error> {
+        if row >= self.kind.key_rows() || col >= self.kind.key_columns() {
+            return Err(Error::InvalidKeyIndex);
+        }
+        Ok(row * self.kind.key_columns() + col)
+    }
+
+    /// Set the button at the provided (row, column) to the provided RGB colour
+    pub fn set_button_rgb_at(&mut self, row: u8, col: u8, colour: &Colour) -> Result<(), Error> {
+        let key = self.key_index(row, col)?;
+        self.set_button_rgb(key, colour)
+    }
+
+    /// Set the button at the provided (row, column) to the provided image
+    pub fn set_button_image_at(
+        &mut self,
+        row: u8,
+        col: u8,
+        image: DynamicImage,
+    ) -> Result<(), Error> {
+        let key = self.key_index(row, col)?;
+        self.set_button_image(key, image)
+    }
+
+    /// Set the button at the provided (row, column) to the provided text
+    pub fn set_button_text_at(
+        &mut self,
+        row: u8,
+        col: u8,
+        font: &FontRef,
+        pos: &TextPosition,
+        text: &str,
+        opts: &TextOptions,
+    ) -> Result<(), Error> {
+        let key = self.key_index(row, col)?;
+        self.set_button_text(key, font, pos, text, opts)
+    }
+
+    /// Transforms a key from zero-indexed left-to-right into the device-correct coordinate system
+    ///
+    /// This is the index used on the wire for image writes, and the position
+    /// of the key in input reports
+    pub fn device_key_index(&self, key: u8) -> Result<u8, Error> {
+        let keys = self.kind.keys();
+
+        // Keys are reversed when the device is mounted upside down
+        let key = match self.rotation == Rotation::Rot180 && key < keys {
+            true => keys - 1 - key,
+            false => key,
+        };
+
+        translate_key_index(self.kind, key)
+    }
+
+    /// Writes an image to a button
+    /// Image at this point in correct dimensions and in device native colour order.
+    ///
+    /// If de-duplication is enabled (see `set_dedupe`) writes of an image identical
+    /// to the last written to the key are skipped.
+    ///
+    /// Images are written over multiple reports, so a failed write (for example
+    /// `Error::Disconnected` if the device is removed) may leave the key partially updated.
+    ///
+    /// Key images are always replaced in full, none of the supported models accept
+    /// writes to a region within a key. Region writes are only supported for the
+    /// Stream Deck Plus touchscreen (see `set_touch_button_image`).
+    ///
+    /// Returns the total number of bytes written over all reports (zero where skipped),
+    /// or `Error::InvalidImageSize` if the image is not in the format for this device.
+    pub fn write_button_image(&mut self, key: u8, image: &DeviceImage) -> Result<usize, Error> {
+        self.check_display()?;
+
+        if !is_device_image(self.kind, &image.data) {
+            return Err(Error::InvalidImageSize);
+        }
+
+        let index = key as usize;
+        let key = self.device_key_index(key)?;
+
+        let hash = match self.dedupe {
+            true => Some(image.hash()),
+            false => None,
+        };
+        if hash.is_some() && self.key_hashes[index] == hash {
+            trace!("skipping duplicate image write for key {}", index);
+            return Ok(0);
+        }
+
+        // Reuse the scratch buffer between writes, zeroed for each image
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.clear();
+        buf.resize(self.image_packet_len(), 0);
+
+        let res = self.write_image_reports(&mut buf, key, &image.data);
+
+        self.buf = buf;
+
+        // Failed writes may leave the key partially updated, so forget the hash
+        self.key_hashes[index] = match res {
+            Ok(n) => {
+                trace!("wrote {} bytes to key {}", n, index);
+                hash
+            }
+            Err(_) => None,
+        };
+
+        if self.track_state {
+            self.key_images[index] = match res {
+                Ok(_) => Some(image.clone()),
+                Err(_) => None,
+            };
+        }
+
+        res
+    }
+
+    /// Set the mounting orientation of the device
+    ///
+    /// The provided rotation and mirroring are applied to images in addition to the
+    /// device transform. When rotated 180 degrees key indices are also reversed, so key 0
+    /// remains at the top left as seen by the user. Dial and touchscreen input is not remapped.
+    pub fn set_orientation(&mut self, rotation: Rotation, mirror: Mirroring) {
+        self.rotation = rotation;
+        self.mirror = mirror;
+        self.clear_key_hashes();
+        self.clear_key_images();
+    }
+
+    /// Fetch the combined mounting and device image transform
+    fn image_transform(&self) -> (Rotation, Mirroring) {
+        compose_transform(
+            (self.rotation, self.mirror),
+            (self.kind.image_rotation(), self.kind.image_mirror()),
+        )
+    }
+
+    /// Write pixel data to a button without applying any transform or colour swap,
+    /// for assets pre-processed to match the device
+    ///
+    /// `pixels` must be `w * h * 3` bytes of row-major pixel data, with the size, colour
+    /// order and orientation expected by the device kind:
+    ///
+    /// | Kind                    | Size    | Colour order | Orientation (from upright)       |
+    /// |-------------------------|---------|--------------|----------------------------------|
+    /// | `Original`              | 72x72   | BGR          | mirrored horizontally            |
+    /// | `OriginalV2`, `Mk2`     | 72x72   | RGB          | mirrored on both axes            |
+    /// | `Mini`, `RevisedMini`   | 80x80   | BGR          | rotated 270 degrees clockwise    |
+    /// | `Xl`                    | 96x96   | RGB          | mirrored on both axes            |
+    /// | `Plus`                  | 120x120 | RGB          | none                             |
+    ///
+    /// Orientations match `Kind::image_rotation` then `Kind::image_mirror`, and the
+    /// orientation set with `set_orientation` is not applied. Data is JPEG encoded where
+    /// required (see `Kind::image_mode`), returning `Error::InvalidImageSize` if
+    /// incorrectly sized.
+    pub fn write_button_image_raw(&mut self, key: u8, pixels: &[u8]) -> Result<(), Error> {
+        self.write_raw_image(key, pixels, false)
+    }
+
+    /// Write raw image data to a button
+    ///
+    /// `bytes` must be correctly sized pixel data in device colour order and orientation
+    /// (see `write_button_image_raw`), which is converted into the device format.
+    /// Where `already_encoded` is set `bytes` are written directly, and must be a JPEG
+    /// image for JPEG devices or pixel data for BMP devices.
+    /// Returns `Error::InvalidImageSize` if the data is not plausible.
+    pub fn write_raw_image(
+        &mut self,
+        key: u8,
+        bytes: &[u8],
+        already_encoded: bool,
+    ) -> Result<(), Error> {
+        if !already_encoded {
+            let image = self.convert_image(bytes.to_vec())?;
+            self.write_button_image(key, &image)?;
+            return Ok(());
+        }
+
+        let image = DeviceImage::from_bytes_checked(self.kind, bytes.to_vec())?;
+        self.write_button_image(key, &image)?;
+        Ok(())
+    }
+
+    /// Set a custom filter for post-processing images (ie. sharpening or dithering),
+    /// `None` removes the filter
+    ///
+    /// The filter is applied to images prepared with `prepare_image` or `load_image`
+    /// (and methods using these) after resizing and background replacement, prior to
+    /// inversion, gamma, device transforms and colour order conversion. Solid colours
+    /// (see `prepare_colour`) are not filtered.
+    pub fn set_image_filter(&mut self, filter: Option<ImageFilter>) {
+        self.image_filter = filter;
+    }
+
+    /// Enable or disable skipping of duplicate image writes (disabled by default)
+    ///
+    /// When enabled a hash of the last image written to each key is retained,
+    /// and writes of an identical image to a key are skipped.
+    ///
+    /// As keys do not support partial updates this is the main saving for animations
+    /// where most keys are static per frame. Images are still prepared (and encoded)
+    /// prior to comparison, however this is small relative to the cost of the transfer.
+    pub fn set_dedupe(&mut self, dedupe: bool) {
+        self.dedupe = dedupe;
+        self.clear_key_hashes();
+    }
+
+    /// Fetch whether duplicate image writes are skipped, see `set_dedupe`
+    pub fn is_dedupe(&self) -> bool {
+        self.dedupe
+    }
+
+    /// Override the length of image reports (including the header), `None` restores
+    /// the default of [Kind::image_report_len]
+    ///
+    /// Smaller reports are slower to write, but may be more reliable over marginal
+    /// USB links. Lengths are clamped to the default report length, and to a minimum
+    /// allowing some data in each report. This is ignored for the original Stream Deck,
+    /// which uses fixed-length reports.
+    pub fn set_max_packet_len(&mut self, len: Option<usize>) {
+        self.max_packet_len = len;
+    }
 
-        match pos {
-            TextPosition::Absolute { x, y } => {
-                let mut y = *y;
-                text.to_string().split("\n").for_each(|txt| {
-                    draw_text_mut(&mut image, colour, *x, y, opts.scale, font, txt);
-                    y += (opts.scale.y * opts.line_height).round() as i32;
-                });
-            }
-        }
+    /// Fetch the length of image reports, see `set_max_packet_len`
+    fn image_packet_len(&self) -> usize {
+        let max = self.kind.image_report_len();
+        let hdrlen = self.kind.image_report_header_len().max(LCD_HEADER_LEN);
+        let min = hdrlen + self.kind.image_base().len() + 1;
 
-        self.set_button_image(key, DynamicImage::ImageRgb8(image))
+        match (self.kind, self.max_packet_len) {
+            (Kind::Original, _) | (_, None) => max,
+            (_, Some(len)) => len.clamp(min, max),
+        }
     }
 
-    ///  Set a button to the provided image file
-    pub fn set_button_file(
-        &mut self,
-        key: u8,
-        image: &str,
-        opts: &ImageOptions,
-    ) -> Result<(), Error> {
-        self.write_button_image(key, &self.load_image(image, opts)?)
+    /// Forget stored key image hashes, for when the device state is unknown
+    fn clear_key_hashes(&mut self) {
+        self.key_hashes.iter_mut().for_each(|h| *h = None);
     }
 
-    /// Load an image file into the device specific representation
-    pub fn load_image(&self, image: &str, opts: &ImageOptions) -> Result<DeviceImage, Error> {
-        let (x, y) = self.kind.image_size();
-        let rotate = self.kind.image_rotation();
-        let mirror = self.kind.image_mirror();
+    /// Enable or disable tracking of the images written to each key (disabled by default)
+    ///
+    /// When enabled the last image written to each key is retained, allowing
+    /// the device state to be captured with `snapshot` and later `restore`d.
+    /// Tracked images are cleared on reset or when the orientation is changed.
+    pub fn track_state(&mut self, track: bool) {
+        self.track_state = track;
+        self.clear_key_images();
+    }
 
-        let image = images::load_image(
-            image,
-            x,
-            y,
-            rotate,
-            mirror,
-            opts,
-            self.kind.image_colour_order(),
-        )?;
-        self.convert_image(image)
+    /// Capture the images last written to each key
+    ///
+    /// Images are only recorded while state tracking is enabled (see `track_state`),
+    /// keys without a recorded image are left unchanged on `restore`.
+    pub fn snapshot(&self) -> DeckSnapshot {
+        DeckSnapshot {
+            images: self.key_images.clone(),
+        }
     }
 
-    /// Transforms a key from zero-indexed left-to-right into the device-correct coordinate system
-    fn translate_key_index(&self, key: u8) -> Result<u8, Error> {
-        if key > self.kind.keys() {
+    /// Re-write the images stored in a snapshot to each key
+    pub fn restore(&mut self, snapshot: &DeckSnapshot) -> Result<(), Error> {
+        if snapshot.images.len() != self.kind.keys() as usize {
             return Err(Error::InvalidKeyIndex);
         }
-        let mapped = match self.kind.key_direction() {
-            // All but the original Streamdeck already have correct coordinates
-            KeyDirection::LeftToRight => key + self.kind.key_index_offset(),
-            // The original Streamdeck uses 1-indexed right-to-left
-            KeyDirection::RightToLeft => {
-                let cols = self.kind.key_columns() as u8;
-                let col = key % cols;
-                let row = key / cols;
-                row * cols + cols - col
+
+        for (key, image) in snapshot.images.iter().enumerate() {
+            if let Some(image) = image {
+                self.write_button_image(key as u8, image)?;
             }
-        };
-        Ok(mapped)
+        }
+
+        Ok(())
     }
 
-    /// Writes an image to a button
-    /// Image at this point in correct dimensions and in device native colour order.
-    pub fn write_button_image(&mut self, key: u8, image: &DeviceImage) -> Result<(), Error> {
-        let image = &image.data;
-        let key = self.translate_key_index(key)?;
+    /// Forget stored key images, for when the device state is unknown
+    fn clear_key_images(&mut self) {
+        self.key_images.iter_mut().for_each(|i| *i = None);
+    }
 
-        let mut buf = vec![0u8; self.kind.image_report_len()];
+    /// Writes image reports for a (translated) key using the provided buffer
+    ///
+    /// Returns the total number of bytes written
+    fn write_image_reports(
+        &mut self,
+        buf: &mut [u8],
+        key: u8,
+        image: &[u8],
+    ) -> Result<usize, Error> {
         let base = self.kind.image_base();
         let hdrlen = self.kind.image_report_header_len();
 
@@ -435,23 +2099,24 @@ impl StreamDeck {
             Kind::Original => {
                 // Original Streamdeck uses static lengths, not the dynamically sized protocol on the
                 // later versions. First packet contains the initial 7749 bytes.
-                self.write_image_header(&mut buf, key, 1, false, 0);
+                self.write_image_header(buf, key, 1, false, 0);
                 let start = hdrlen + base.len();
                 buf[hdrlen..start].copy_from_slice(base);
                 buf[start..start + 7749].copy_from_slice(&image[0..7749]);
-                self.device.write(&buf)?;
+                let mut written = self.device.write(buf)?;
 
                 // Second packet contains the last 7803 bytes
-                self.write_image_header(&mut buf, key, 2, true, 0);
+                self.write_image_header(buf, key, 2, true, 0);
                 buf[hdrlen..hdrlen + 7803].copy_from_slice(&image[7749..15552]);
-                self.device.write(&buf)?;
+                written += self.device.write(buf)?;
 
-                Ok(())
+                Ok(written)
             }
 
             _ => {
                 let mut sequence = 0;
                 let mut offset = 0;
+                let mut written = 0;
                 let maxdatalen = buf.len() - hdrlen;
 
                 while offset < image.len() {
@@ -467,7 +2132,7 @@ impl StreamDeck {
                     }
 
                     let is_last = take == image.len() - offset;
-                    self.write_image_header(&mut buf, key, sequence, is_last, take);
+                    self.write_image_header(buf, key, sequence, is_last, take);
                     buf[start..start + take].copy_from_slice(&image[offset..offset + take]);
 
                     trace!(
@@ -479,12 +2144,12 @@ impl StreamDeck {
                         sequence,
                         if is_last { " (last)" } else { "" },
                     );
-                    self.device.write(&buf)?;
+                    written += self.device.write(buf)?;
 
                     sequence += 1;
                     offset += take;
                 }
-                Ok(())
+                Ok(written)
             }
         }
     }
@@ -516,17 +2181,69 @@ impl StreamDeck {
 }
 
 /// TextPosition is how to position text via set_button_text
+#[derive(Debug, Clone, PartialEq)]
 pub enum TextPosition {
     /// Absolute positioning
     Absolute { x: i32, y: i32 },
+    /// Positioning relative to an anchor on the key, with an offset in pixels.
+    /// Each line of text is aligned horizontally to the anchor.
+    Anchored { anchor: Anchor, offset: (i32, i32) },
+}
+
+impl TextPosition {
+    /// Text centered on the key
+    pub fn centered() -> Self {
+        TextPosition::Anchored {
+            anchor: Anchor::Center,
+            offset: (0, 0),
+        }
+    }
+}
+
+/// Anchor points for [TextPosition::Anchored]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Compute the origin of a box of `size` anchored within an area of `area`
+    fn origin(&self, area: (u32, u32), size: (u32, u32)) -> (i32, i32) {
+        // Alignment in halves of the free space (start, center, end)
+        let (h, v) = match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::TopCenter => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::CenterLeft => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::CenterRight => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::BottomCenter => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+
+        let x = (area.0 as i32 - size.0 as i32) * h / 2;
+        let y = (area.1 as i32 - size.1 as i32) * v / 2;
+        (x, y)
+    }
 }
 
 /// Text Options provide values for text buttons
+#[derive(Debug, Clone)]
 pub struct TextOptions {
     foreground: Colour,
     background: Colour,
     scale: PxScale,
     line_height: f32,
+    paragraph_spacing: f32,
 }
 
 impl TextOptions {
@@ -536,7 +2253,65 @@ impl TextOptions {
             background,
             scale,
             line_height,
+            paragraph_spacing: 0.0,
+        }
+    }
+
+    /// Set the foreground (text) colour
+    pub fn with_foreground(mut self, foreground: Colour) -> Self {
+        self.foreground = foreground;
+        self
+    }
+
+    /// Set the background colour
+    pub fn with_background(mut self, background: Colour) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Set the text scale (height in pixels)
+    pub fn with_scale(mut self, scale: PxScale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Set a non-uniform text scale, with the glyph width and height in pixels,
+    /// for condensed or expanded text
+    pub fn with_scale_xy(mut self, x: f32, y: f32) -> Self {
+        self.scale = PxScale { x, y };
+        self
+    }
+
+    /// Set the line height as a multiple of the text scale
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    /// Set additional spacing (in pixels) between paragraphs,
+    /// added for each blank line separating lines of text
+    pub fn with_paragraph_spacing(mut self, paragraph_spacing: f32) -> Self {
+        self.paragraph_spacing = paragraph_spacing;
+        self
+    }
+
+    /// Compute the vertical offset of each line from the top of the text
+    fn line_offsets(&self, lines: &[&str]) -> Vec<i32> {
+        let step = (self.scale.y * self.line_height).round() as i32;
+        let spacing = self.paragraph_spacing.round() as i32;
+
+        let mut y = 0;
+        let mut offsets = vec![];
+        for txt in lines {
+            offsets.push(y);
+
+            y += step;
+            if txt.trim().is_empty() {
+                y += spacing;
+            }
         }
+
+        offsets
     }
 }
 
@@ -549,13 +2324,821 @@ impl Default for TextOptions {
             background: Colour::from_str("000000").unwrap(),
             scale: PxScale { x: 15.0, y: 15.0 },
             line_height: 1.1,
+            paragraph_spacing: 0.0,
+        }
+    }
+}
+
+/// Shape Options provide colours for shape buttons
+pub struct ShapeOptions {
+    fill: Colour,
+    border: Option<(Colour, u32)>,
+    background: Colour,
+}
+
+impl ShapeOptions {
+    /// Create shape options with the provided fill, an optional border
+    /// colour and width (in pixels), and a background colour
+    pub fn new(fill: Colour, border: Option<(Colour, u32)>, background: Colour) -> Self {
+        ShapeOptions {
+            fill,
+            border,
+            background,
+        }
+    }
+}
+
+impl Default for ShapeOptions {
+    /// default is a white shape with no border on a black background
+    fn default() -> Self {
+        ShapeOptions {
+            fill: Colour::from_str("FFFFFF").unwrap(),
+            border: None,
+            background: Colour::from_str("000000").unwrap(),
+        }
+    }
+}
+
+/// Convert an image into the device dependent format
+fn convert_image(kind: Kind, image: Vec<u8>) -> Result<DeviceImage, Error> {
+    // Check image dimensions, this is the single point at which the
+    // length is validated for all image sources
+    if image.len() != kind.image_size_bytes() {
+        return Err(Error::InvalidImageSize);
+    }
+    let image = match kind.image_mode() {
+        ImageMode::Bmp => image,
+        ImageMode::Jpeg => kind.encode_image(&image)?,
+    };
+    Ok(DeviceImage { data: image })
+}
+
+/// HID usage page of the primary Stream Deck interface (consumer)
+const STREAMDECK_USAGE_PAGE: u16 = 0x000c;
+
+/// Rank device interfaces for selection, lower is preferred
+///
+/// Usage information is unavailable on some platforms (ie. the linux libusb backend)
+/// in which case this is reported as zero, so this is preferred over interfaces
+/// with a known mismatched usage page.
+fn interface_rank(info: &DeviceInfo) -> u8 {
+    match info.usage_page() {
+        STREAMDECK_USAGE_PAGE => 0,
+        0 => 1,
+        _ => 2,
+    }
+}
+
+/// Restores the blocking mode of a device when dropped, see `StreamDeck::with_blocking`
+struct BlockingGuard<'a> {
+    deck: &'a mut StreamDeck,
+    prior: bool,
+}
+
+impl Drop for BlockingGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.deck.set_blocking(self.prior) {
+            warn!("Failed to restore blocking mode: {:?}", e);
+        }
+    }
+}
+
+/// Open a device by VID / PID and optional serial, selecting the primary interface
+/// where a device exposes several, and falling back to hidapi device matching
+fn open_interface(
+    api: &HidApi,
+    vid: u16,
+    pid: u16,
+    serial: Option<&str>,
+) -> Result<HidDevice, Error> {
+    let info = api
+        .device_list()
+        .filter(|d| d.vendor_id() == vid && d.product_id() == pid)
+        .filter(|d| serial.is_none() || d.serial_number() == serial)
+        .min_by_key(|d| interface_rank(d));
+
+    let device = match (info, serial) {
+        (Some(i), _) => i.open_device(api),
+        (None, Some(s)) => api.open_serial(vid, pid, s),
+        (None, None) => api.open(vid, pid),
+    }?;
+
+    Ok(device)
+}
+
+/// Check whether data is plausibly an image in the device format
+fn is_device_image(kind: Kind, data: &[u8]) -> bool {
+    match kind.image_mode() {
+        ImageMode::Bmp => data.len() == kind.image_size_bytes(),
+        // Check for JPEG start and end of image markers
+        ImageMode::Jpeg => {
+            data.len() >= 4 && data.starts_with(&[0xff, 0xd8]) && data.ends_with(&[0xff, 0xd9])
         }
     }
 }
 
+/// Resize an image and apply options and device transforms, then convert
+/// into the device dependent format
+fn prepare_image(
+    kind: Kind,
+    transform: (Rotation, Mirroring),
+    filter: Option<&ImageFilter>,
+    image: DynamicImage,
+    opts: &ImageOptions,
+) -> Result<DeviceImage, Error> {
+    let image = prepare_pixels(kind, transform, filter, image, opts)?;
+    convert_image(kind, image)
+}
+
+/// Resize and transform an image into device pixel data, prior to encoding
+fn prepare_pixels(
+    kind: Kind,
+    transform: (Rotation, Mirroring),
+    filter: Option<&ImageFilter>,
+    image: DynamicImage,
+    opts: &ImageOptions,
+) -> Result<Vec<u8>, Error> {
+    let (x, y) = kind.image_size();
+
+    images::process_image(
+        image,
+        x,
+        y,
+        transform,
+        opts,
+        filter,
+        kind.image_colour_order(),
+    )
+}
+
+/// Transforms a key from zero-indexed left-to-right into the device-correct coordinate system
+fn translate_key_index(kind: Kind, key: u8) -> Result<u8, Error> {
+    if !kind.is_valid_key(key) {
+        return Err(Error::InvalidKeyIndex);
+    }
+    let mapped = match kind.key_direction() {
+        // All but the original Streamdeck already have correct coordinates
+        KeyDirection::LeftToRight => key + kind.key_index_offset(),
+        // The original Streamdeck uses 1-indexed right-to-left
+        KeyDirection::RightToLeft => {
+            let cols = kind.key_columns();
+            let col = key % cols;
+            let row = key / cols;
+            row * cols + cols - col
+        }
+    };
+    Ok(mapped)
+}
+
 // Convert RGB image data to BGR
-fn rgb_to_bgr(data: &mut Vec<u8>) {
+fn rgb_to_bgr(data: &mut [u8]) {
     for chunk in data.chunks_exact_mut(3) {
         chunk.swap(0, 2);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KINDS: [Kind; 7] = [
+        Kind::Original,
+        Kind::OriginalV2,
+        Kind::Mini,
+        Kind::RevisedMini,
+        Kind::Xl,
+        Kind::Mk2,
+        Kind::Plus,
+    ];
+
+    /// Create a device of the provided kind using a [MockDevice] transport,
+    /// returning the mock handle for inspecting writes and queueing input
+    pub(crate) fn mock_deck(kind: Kind) -> (StreamDeck, MockDevice) {
+        let mock = MockDevice::new();
+        let deck = StreamDeck::with_transport(kind, Box::new(mock.clone()));
+        (deck, mock)
+    }
+
+    #[test]
+    fn key_index_bounds() {
+        for kind in KINDS {
+            let last = kind.keys() - 1;
+            assert!(translate_key_index(kind, last).is_ok(), "{:?}", kind);
+            assert!(
+                matches!(
+                    translate_key_index(kind, kind.keys()),
+                    Err(Error::InvalidKeyIndex)
+                ),
+                "{:?}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn input_report_lens() {
+        let expected = [16, 19, 7, 8, 36, 19, 14];
+
+        for (kind, len) in KINDS.iter().zip(expected) {
+            assert_eq!(kind.input_report_len(), len, "{:?}", kind);
+            assert!(len <= INPUT_REPORT_LEN, "{:?}", kind);
+
+            // Reports must contain the state of every key
+            let index_offset = kind.key_index_offset() as usize;
+            assert!(len >= 1 + kind.key_data_offset() + index_offset + kind.keys() as usize);
+        }
+    }
+
+    #[test]
+    fn revised_mini_offset() {
+        let (mut deck, mock) = mock_deck(Kind::RevisedMini);
+
+        // Writes to key 0 use device index 1
+        deck.set_button_rgb(0, &Colour { r: 0, g: 0, b: 0 })
+            .unwrap();
+        assert_eq!(mock.writes()[0][5], 1);
+
+        // Reads of device index 1 report key 0
+        mock.push_input(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let buttons = deck.read_buttons(None).unwrap();
+        assert_eq!(buttons, vec![1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reconnect_custom_transport() {
+        let (mut deck, _) = mock_deck(Kind::Mini);
+        assert!(matches!(deck.reconnect(), Err(Error::UnsupportedInput)));
+    }
+
+    #[test]
+    fn pid_aliases() {
+        assert_eq!(kind_from_pid(pids::XL_V2), Some(Kind::Xl));
+        assert_eq!(kind_from_pid(pids::MK2_SCISSOR), Some(Kind::Mk2));
+        assert_eq!(kind_from_pid(pids::MINI_DISCORD), Some(Kind::RevisedMini));
+        assert_eq!(kind_from_pid(0xffff), None);
+
+        assert_eq!(
+            Error::UnrecognisedPID(0x00a6).to_string(),
+            "unrecognised pid (0x00a6)"
+        );
+    }
+
+    #[test]
+    fn map_disconnect_errors() {
+        let e = HidError::HidApiError {
+            message: "hid_write: No such device".to_string(),
+        };
+        assert!(matches!(Error::from(e), Error::Disconnected));
+
+        let e = HidError::HidApiError {
+            message: "invalid report".to_string(),
+        };
+        assert!(matches!(Error::from(e), Error::Hid(_)));
+
+        // Generic I/O failures are not disconnections
+        let e = HidError::HidApiError {
+            message: "hid_write: Input/output error".to_string(),
+        };
+        assert!(matches!(Error::from(e), Error::Hid(_)));
+
+        let error = std::io::Error::from_raw_os_error(DISCONNECT_CODES[0]);
+        let e = HidError::IoError { error };
+        assert!(matches!(Error::from(e), Error::Disconnected));
+
+        let error = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        let e = HidError::IoError { error };
+        assert!(matches!(Error::from(e), Error::Hid(_)));
+    }
+
+    #[test]
+    fn sleep_wake() {
+        let (mut deck, mock) = mock_deck(Kind::Mk2);
+        let last_brightness = || mock.feature_reports().last().map(|r| r[..3].to_vec());
+
+        // Wake restores full brightness by default
+        deck.wake().unwrap();
+        assert_eq!(last_brightness(), Some(vec![0x03, 0x08, 100]));
+
+        // Sleep blanks the display without changing the stored brightness
+        deck.set_brightness(40).unwrap();
+        deck.sleep().unwrap();
+        assert_eq!(last_brightness(), Some(vec![0x03, 0x08, 0]));
+        assert_eq!(deck.brightness(), 40);
+
+        deck.wake().unwrap();
+        assert_eq!(last_brightness(), Some(vec![0x03, 0x08, 40]));
+    }
+
+    #[test]
+    fn standby_timeouts() {
+        let (mut deck, mock) = mock_deck(Kind::Mk2);
+
+        deck.set_standby_timeout(Some(300)).unwrap();
+        deck.set_standby_timeout(None).unwrap();
+
+        let mut enabled = vec![0u8; 17];
+        enabled[..6].copy_from_slice(&[0x03, 0x0d, 0x2c, 0x01, 0x00, 0x00]);
+        let mut disabled = vec![0u8; 17];
+        disabled[..2].copy_from_slice(&[0x03, 0x0d]);
+        assert_eq!(mock.feature_reports(), vec![enabled, disabled]);
+
+        for kind in [Kind::Original, Kind::OriginalV2, Kind::Mini] {
+            let (mut deck, mock) = mock_deck(kind);
+            assert!(matches!(
+                deck.set_standby_timeout(Some(300)),
+                Err(Error::UnsupportedInput)
+            ));
+            assert!(mock.feature_reports().is_empty());
+        }
+    }
+
+    #[test]
+    fn with_blocking_restores() {
+        let (mut deck, _) = mock_deck(Kind::Mini);
+
+        let blocking = deck.with_blocking(false, |d| d.is_blocking()).unwrap();
+        assert!(!blocking);
+        assert!(deck.is_blocking());
+
+        // Prior mode is also restored when the closure panics
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            deck.with_blocking(false, |_| panic!("read failed"))
+        }));
+        assert!(res.is_err());
+        assert!(deck.is_blocking());
+    }
+
+    #[test]
+    fn reset_reports() {
+        for kind in KINDS {
+            let (mut deck, mock) = mock_deck(kind);
+            deck.reset().unwrap();
+
+            let mut expected = vec![0u8; kind.feature_report_len()];
+            match kind.is_v2() {
+                true => expected[..2].copy_from_slice(&[0x03, 0x02]),
+                false => expected[..2].copy_from_slice(&[0x0b, 0x63]),
+            }
+            assert_eq!(mock.feature_reports(), vec![expected], "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn anchor_origins() {
+        let area = (72, 72);
+        let size = (30, 10);
+
+        assert_eq!(Anchor::TopLeft.origin(area, size), (0, 0));
+        assert_eq!(Anchor::Center.origin(area, size), (21, 31));
+        assert_eq!(Anchor::BottomCenter.origin(area, size), (21, 62));
+        assert_eq!(Anchor::BottomRight.origin(area, size), (42, 62));
+
+        // Oversized text is centered with a negative origin
+        assert_eq!(Anchor::Center.origin(area, (80, 10)), (-4, 31));
+    }
+
+    #[test]
+    fn key_rects() {
+        for kind in KINDS {
+            let (w, h) = kind.canvas_size();
+            let last = kind.key_rect(kind.keys() - 1).unwrap();
+            assert_eq!((last.0 + last.2, last.1 + last.3), (w, h), "{:?}", kind);
+            assert_eq!(kind.key_rect(kind.keys()), None);
+        }
+
+        assert_eq!(Kind::Mk2.key_rect(0), Some((0, 0, 72, 72)));
+        assert_eq!(Kind::Mk2.key_rect(6), Some((108, 108, 72, 72)));
+    }
+
+    #[test]
+    fn mosaic_tiles() {
+        let (mut deck, _) = mock_deck(Kind::Mk2);
+
+        // Left half red, right half blue
+        let image = ImageBuffer::from_fn(10, 6, |x, _| match x < 5 {
+            true => Rgb([255, 0, 0]),
+            false => Rgb([0, 0, 255]),
+        });
+        deck.track_state(true);
+        deck.set_mosaic(DynamicImage::ImageRgb8(image), &ImageOptions::default())
+            .unwrap();
+
+        let snapshot = deck.snapshot();
+        for key in 0..Kind::Mk2.keys() {
+            assert!(snapshot.image(key).is_some(), "{}", key);
+        }
+
+        let red = deck.prepare_colour(&Colour::from_str("FF0000").unwrap());
+        assert!(snapshot.image(0) == Some(&red.unwrap()));
+    }
+
+    #[test]
+    fn max_packet_len() {
+        for kind in [Kind::Mini, Kind::Xl] {
+            let (mut deck, mock) = mock_deck(kind);
+            let image = deck.prepare_colour(&Colour::from_str("FF0000").unwrap());
+            let image = image.unwrap();
+
+            deck.write_button_image(0, &image).unwrap();
+            let writes = mock.writes();
+            assert!(writes.iter().all(|w| w.len() == kind.image_report_len()));
+
+            // Reports are split without changing the data written
+            mock.clear();
+            deck.set_max_packet_len(Some(256));
+            deck.write_button_image(0, &image).unwrap();
+            let small = mock.writes();
+            assert!(small.iter().all(|w| w.len() == 256));
+            assert!(small.len() > writes.len());
+
+            let hdrlen = kind.image_report_header_len();
+            let data = |w: &[Vec<u8>]| {
+                w.iter().fold(0, |n, w| n + w.len() - hdrlen) - kind.image_base().len()
+            };
+            assert!(data(&small) >= image.data.len());
+
+            deck.set_max_packet_len(Some(1));
+            assert_eq!(deck.image_packet_len(), 16 + kind.image_base().len() + 1);
+            deck.set_max_packet_len(Some(1 << 16));
+            assert_eq!(deck.image_packet_len(), kind.image_report_len());
+        }
+    }
+
+    #[test]
+    fn checked_brightness() {
+        let (mut deck, mock) = mock_deck(Kind::Mk2);
+
+        deck.try_set_brightness(100).unwrap();
+        assert!(matches!(
+            deck.try_set_brightness(150),
+            Err(Error::InvalidBrightness(150))
+        ));
+        assert_eq!(deck.brightness(), 100);
+        assert_eq!(mock.feature_reports().len(), 1);
+    }
+
+    #[test]
+    fn device_images() {
+        let black = Colour::from_str("000000").unwrap();
+
+        for kind in KINDS {
+            let blank = DeviceImage::blank(kind).unwrap();
+            let expected = convert_image(kind, kind.colour_image(&black)).unwrap();
+            assert!(blank == expected, "{:?}", kind);
+
+            assert!(DeviceImage::from_bytes_checked(kind, blank.data.clone()).is_ok());
+            assert!(matches!(
+                DeviceImage::from_bytes_checked(kind, vec![0u8; 10]),
+                Err(Error::InvalidImageSize)
+            ));
+        }
+
+        // Data sized for another kind is rejected
+        let xl = Kind::Xl.colour_image(&black);
+        assert!(DeviceImage::from_bytes_checked(Kind::Mini, xl.clone()).is_err());
+
+        let (mut deck, _) = mock_deck(Kind::Mini);
+        assert!(matches!(
+            deck.write_button_image(0, &DeviceImage::from_bytes(xl)),
+            Err(Error::InvalidImageSize)
+        ));
+    }
+
+    #[test]
+    fn ping_device() {
+        let (mut deck, mock) = mock_deck(Kind::Mk2);
+        assert!(matches!(deck.ping(), Err(Error::Disconnected)));
+
+        mock.set_feature_response(&[0x05, 0x0c, 0x00, 0x00, 0x00, 0x00, b'1', b'.', b'0']);
+        deck.ping().unwrap();
+    }
+
+    #[test]
+    fn text_scale_xy() {
+        let opts = TextOptions::default().with_scale_xy(10.0, 20.0);
+        assert_eq!(opts.scale, PxScale { x: 10.0, y: 20.0 });
+    }
+
+    #[test]
+    fn text_line_offsets() {
+        let opts = TextOptions::default().with_scale(PxScale::from(10.0));
+        assert_eq!(opts.line_offsets(&["a", "b", "c"]), vec![0, 11, 22]);
+
+        let opts = opts.with_line_height(1.0).with_paragraph_spacing(4.0);
+        assert_eq!(opts.line_offsets(&["a", "", "b", "c"]), vec![0, 10, 24, 34]);
+    }
+
+    #[test]
+    fn touch_button_reports() {
+        let (mut deck, mock) = mock_deck(Kind::Plus);
+
+        let image = DynamicImage::new_rgb8(10, 10);
+        deck.set_touch_button_image(1, image).unwrap();
+
+        let writes = mock.writes();
+        assert!(!writes.is_empty());
+        assert_eq!(
+            &writes[0][..10],
+            &[0x02, 0x0c, 200, 0, 0, 0, 200, 0, 100, 0]
+        );
+        assert_eq!(writes.last().unwrap()[10], 1);
+
+        let (mut mini, _) = mock_deck(Kind::Mini);
+        assert!(matches!(
+            mini.set_touch_button_image(0, DynamicImage::new_rgb8(10, 10)),
+            Err(Error::UnsupportedInput)
+        ));
+    }
+
+    #[test]
+    fn acknowledge_dials() {
+        let (mut deck, mock) = mock_deck(Kind::Plus);
+
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(10, 10, Rgb([0, 0, 255])));
+        deck.set_touch_button_image(2, image).unwrap();
+        let previous = mock.writes();
+        mock.clear();
+
+        let red = Colour { r: 255, g: 0, b: 0 };
+        deck.acknowledge_dial(2, &red, Duration::from_millis(0))
+            .unwrap();
+
+        // Flash then restore, both within the region above the dial
+        let writes = mock.writes();
+        assert!(writes.len() > previous.len());
+        assert!(writes.iter().all(|w| w[2..4] == [144, 1]));
+        assert_eq!(&writes[writes.len() - previous.len()..], &previous[..]);
+
+        assert!(matches!(
+            deck.acknowledge_dial(4, &red, Duration::from_millis(0)),
+            Err(Error::InvalidKeyIndex)
+        ));
+
+        let (mut mini, _) = mock_deck(Kind::Mini);
+        assert!(matches!(
+            mini.acknowledge_dial(0, &red, Duration::from_millis(0)),
+            Err(Error::UnsupportedInput)
+        ));
+    }
+
+    #[test]
+    fn firmware_versions() {
+        let v: FirmwareVersion = "1.01.000\0\0\0".parse().unwrap();
+        assert_eq!(v, FirmwareVersion::new(1, 1, 0));
+        assert_eq!(v, "1.01".parse().unwrap());
+        assert!(v >= FirmwareVersion::new(1, 1, 0));
+        assert!(v < "1.10".parse().unwrap());
+        assert!(v < FirmwareVersion::new(2, 0, 0));
+        assert_eq!(v.to_string(), "1.1.0");
+
+        assert!("".parse::<FirmwareVersion>().is_err());
+        assert!("1.a".parse::<FirmwareVersion>().is_err());
+        assert!("1.2.3.4".parse::<FirmwareVersion>().is_err());
+    }
+
+    #[test]
+    fn button_handles() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+
+        let red = Colour { r: 255, g: 0, b: 0 };
+        deck.button(5).unwrap().set_colour(&red).unwrap();
+        assert!(!mock.writes().is_empty());
+
+        assert!(matches!(deck.button(6), Err(Error::InvalidKeyIndex)));
+    }
+
+    #[test]
+    fn valid_keys() {
+        for kind in &KINDS {
+            let (deck, _) = mock_deck(*kind);
+            let last = kind.keys() - 1;
+
+            assert!(deck.is_valid_key(0));
+            assert!(deck.is_valid_key(last));
+            assert!(!deck.is_valid_key(last + 1));
+            assert!(deck.device_key_index(last).is_ok());
+            assert!(deck.device_key_index(last + 1).is_err());
+        }
+    }
+
+    #[test]
+    fn default_timeouts() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+        assert_eq!(deck.default_timeout(), None);
+
+        deck.set_default_timeout(Some(Duration::from_millis(10)));
+        assert_eq!(deck.default_timeout(), Some(Duration::from_millis(10)));
+        assert!(matches!(deck.read_buttons_default(), Err(Error::NoData)));
+
+        mock.push_input(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(deck.read_input_default().unwrap()[2], 0x01);
+    }
+
+    #[test]
+    fn toggle_buttons() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+
+        let on = deck.prepare_colour(&Colour { r: 0, g: 255, b: 0 }).unwrap();
+        let off = deck.prepare_colour(&Colour { r: 0, g: 0, b: 0 }).unwrap();
+        let mut toggle = ToggleButton::new(on.clone(), off);
+        assert!(!toggle.state());
+
+        assert!(toggle.toggle(&mut deck, 1).unwrap());
+        let writes = mock.writes();
+        mock.clear();
+        deck.write_button_image(1, &on).unwrap();
+        assert_eq!(mock.writes(), writes);
+
+        toggle.set(&mut deck, 1, false).unwrap();
+        assert!(!toggle.state());
+        assert!(toggle.set(&mut deck, 6, true).is_err());
+        assert!(!toggle.state());
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+        deck.track_state(true);
+
+        let red = Colour { r: 255, g: 0, b: 0 };
+        deck.set_button_rgb(2, &red).unwrap();
+
+        let snapshot = deck.snapshot();
+        assert!(snapshot.image(0).is_none());
+        assert!(snapshot.image(2) == Some(&deck.prepare_colour(&red).unwrap()));
+
+        let writes = mock.writes();
+        deck.clear_all().unwrap();
+        mock.clear();
+
+        deck.restore(&snapshot).unwrap();
+        assert_eq!(mock.writes(), writes);
+    }
+
+    #[test]
+    fn colour_gamma() {
+        let (mut deck, _) = mock_deck(Kind::Mini);
+        let colour = Colour::from_str("FF8000").unwrap();
+        let corrected = Colour::from_str("FF4000").unwrap();
+
+        deck.set_colour_gamma(Some(2.0));
+        let expected = deck.convert_image(Kind::Mini.colour_image(&corrected));
+        assert!(deck.prepare_colour(&colour).unwrap() == expected.unwrap());
+
+        deck.set_colour_gamma(None);
+        let expected = deck.convert_image(Kind::Mini.colour_image(&colour));
+        assert!(deck.prepare_colour(&colour).unwrap() == expected.unwrap());
+    }
+
+    /// Decode the first pixel of an image written to a mock device
+    fn written_pixel(kind: Kind, writes: &[Vec<u8>]) -> [u8; 3] {
+        let hdrlen = kind.image_report_header_len();
+
+        match kind.image_mode() {
+            // Pixel data follows the BMP header in the first report, in BGR order
+            ImageMode::Bmp => {
+                let start = hdrlen + kind.image_base().len();
+                let p = &writes[0][start..start + 3];
+                [p[2], p[1], p[0]]
+            }
+            // Reassemble and decode the JPEG payload
+            ImageMode::Jpeg => {
+                let mut data = vec![];
+                for w in writes {
+                    let len = u16::from_le_bytes([w[4], w[5]]) as usize;
+                    data.extend_from_slice(&w[hdrlen..hdrlen + len]);
+                }
+                let image = image::load_from_memory(&data).unwrap().into_rgb8();
+                image.get_pixel(0, 0).0
+            }
+        }
+    }
+
+    #[test]
+    fn bmp_headers() {
+        let u32_at = |h: &[u8], i: usize| {
+            let mut b = [0u8; 4];
+            b.copy_from_slice(&h[i..i + 4]);
+            u32::from_le_bytes(b)
+        };
+
+        for kind in KINDS.iter().filter(|k| k.image_mode() == ImageMode::Bmp) {
+            let header = kind.image_base();
+            let (w, h) = kind.image_size();
+            let len = kind.image_size_bytes() as u32;
+
+            assert_eq!(&header[..2], b"BM", "{:?}", kind);
+            assert_eq!(u32_at(header, 2), header.len() as u32 + len, "{:?}", kind);
+            assert_eq!(u32_at(header, 10), header.len() as u32, "{:?}", kind);
+            assert_eq!(u32_at(header, 18), w as u32, "{:?}", kind);
+            assert_eq!(u32_at(header, 22), h as u32, "{:?}", kind);
+            assert_eq!(&header[28..30], &[24, 0], "{:?}", kind);
+            assert_eq!(u32_at(header, 34), len, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn encode_kind_images() {
+        for kind in &KINDS {
+            let pixels = [10, 200, 30].repeat(kind.image_size_bytes() / 3);
+            let (deck, _) = mock_deck(*kind);
+
+            let encoded = kind.encode_image(&pixels).unwrap();
+            let image = deck.convert_image(pixels.clone()).unwrap();
+            assert_eq!(encoded, image.data, "{:?}", kind);
+
+            assert!(kind.encode_image(&pixels[3..]).is_err());
+        }
+    }
+
+    #[test]
+    fn prepared_pixels() {
+        for kind in &KINDS {
+            let (mut deck, mock) = mock_deck(*kind);
+            let opts = ImageOptions::default();
+            let image = DynamicImage::ImageRgb8(ImageBuffer::from_fn(20, 20, |x, y| {
+                Rgb([x as u8 * 10, y as u8 * 10, 128])
+            }));
+
+            let pixels = deck.prepare_pixels(image.clone(), &opts).unwrap();
+            assert_eq!(pixels.dimensions().0 as usize, kind.image_size().0);
+
+            let prepared = deck.prepare_image(image, &opts).unwrap();
+            let encoded = kind.encode_image(pixels.as_raw()).unwrap();
+            assert_eq!(prepared.as_bytes(), encoded);
+
+            // Writing the raw pixels matches writing the prepared image
+            deck.write_button_image(0, &prepared).unwrap();
+            let writes = mock.writes();
+            mock.clear();
+            deck.write_button_image_raw(0, pixels.as_raw()).unwrap();
+            assert_eq!(mock.writes(), writes, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn image_filters() {
+        let (mut deck, _) = mock_deck(Kind::Mini);
+        let image = || DynamicImage::new_rgb8(10, 10);
+        let opts = ImageOptions::default();
+
+        // Filter sees the upright resized image, before the device transform
+        deck.set_image_filter(Some(Box::new(|i: &mut RgbImage| {
+            assert_eq!(i.dimensions(), (80, 80));
+            i.put_pixel(0, 0, Rgb([255, 255, 255]));
+        })));
+        let pixels = deck.prepare_pixels(image(), &opts).unwrap();
+        let white = pixels.pixels().filter(|p| p[0] == 255).count();
+        assert_eq!(white, 1);
+
+        deck.set_image_filter(None);
+        let pixels = deck.prepare_pixels(image(), &opts).unwrap();
+        assert!(pixels.pixels().all(|p| p[0] == 0));
+    }
+
+    #[test]
+    fn raw_button_images() {
+        for kind in &KINDS {
+            let (mut deck, mock) = mock_deck(*kind);
+
+            let pixels = [10, 200, 30].repeat(kind.image_size_bytes() / 3);
+            deck.write_button_image_raw(0, &pixels).unwrap();
+
+            // Pixel data is written without a colour swap
+            let p = written_pixel(*kind, &mock.writes());
+            let p = match kind.image_mode() {
+                ImageMode::Bmp => [p[2], p[1], p[0]],
+                ImageMode::Jpeg => p,
+            };
+            for (a, b) in p.iter().zip([10, 200, 30]) {
+                assert!((*a as i32 - b).abs() <= 8, "{:?} {:?}", kind, p);
+            }
+
+            assert!(matches!(
+                deck.write_button_image_raw(0, &pixels[1..]),
+                Err(Error::InvalidImageSize)
+            ));
+        }
+    }
+
+    #[test]
+    fn colour_order() {
+        let red = Colour { r: 255, g: 0, b: 0 };
+
+        for kind in [Kind::Mini, Kind::Mk2] {
+            let (mut deck, mock) = mock_deck(kind);
+
+            deck.set_button_rgb(0, &red).unwrap();
+            let p = written_pixel(kind, &mock.writes());
+            assert!(p[0] > 250 && p[1] < 5 && p[2] < 5, "{:?}: {:?}", kind, p);
+
+            mock.clear();
+            let (w, h) = kind.image_size();
+            let image = ImageBuffer::from_pixel(w as u32, h as u32, Rgb([255, 0, 0]));
+            deck.set_button_image(0, DynamicImage::ImageRgb8(image))
+                .unwrap();
+            let p = written_pixel(kind, &mock.writes());
+            assert!(p[0] > 250 && p[1] < 5 && p[2] < 5, "{:?}: {:?}", kind, p);
+        }
+    }
+}
@@ -9,7 +9,12 @@ use structopt::StructOpt;
 extern crate humantime;
 use humantime::Duration;
 
-use streamdeck::{StreamDeck, Filter, Colour, ImageOptions, Error};
+use std::ffi::CString;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use streamdeck::{StreamDeck, ELGATO_VID, Anchor, Filter, Colour, ImageOptions, Error, InputEvent, InputManager, Layout, TextOptions, TextPosition};
+use ab_glyph::{FontRef, PxScale};
 
 #[derive(StructOpt)]
 #[structopt(name = "streamdeck-cli", about = "A CLI for the Elgato StreamDeck")]
@@ -24,6 +29,10 @@ struct Options {
     #[structopt(long = "log-level", default_value = "info")]
     /// Enable verbose logging
     level: LevelFilter,
+
+    #[structopt(long)]
+    /// Output probe results and input events as newline-delimited JSON
+    json: bool,
 }
 
 #[derive(StructOpt)]
@@ -39,6 +48,14 @@ pub enum Commands {
         /// Brightness value from 0 to 100
         brightness: u8,
     },
+    /// Fade device display brightness
+    Fade {
+        /// Target brightness value from 0 to 100
+        target: u8,
+
+        /// Duration of the fade (ie. `2s`, `500ms`)
+        duration: Duration,
+    },
     /// Fetch button states
     GetButtons {
         #[structopt(long)]
@@ -46,8 +63,26 @@ pub enum Commands {
         timeout: Option<Duration>,
 
         #[structopt(long)]
-        /// Read continuously
+        /// Read continuously, until interrupted with Ctrl-C
         continuous: bool,
+
+        #[structopt(long)]
+        /// Delay between reads in continuous mode
+        interval: Option<Duration>,
+    },
+    /// Fetch input events (buttons, dials and touchscreen)
+    GetInput {
+        #[structopt(long)]
+        /// Timeout for input reading
+        timeout: Option<Duration>,
+
+        #[structopt(long)]
+        /// Read continuously, until interrupted with Ctrl-C
+        continuous: bool,
+
+        #[structopt(long)]
+        /// Delay between reads in continuous mode
+        interval: Option<Duration>,
     },
     /// Set button colours
     SetColour {
@@ -57,6 +92,18 @@ pub enum Commands {
         #[structopt(flatten)]
         colour: Colour,
     },
+    /// Apply a layout from a JSON or TOML file
+    Apply {
+        /// Layout file, parsed as TOML for files with a `.toml` extension or as JSON otherwise
+        file: String,
+    },
+    /// Clear all buttons
+    Clear,
+    /// Fill all buttons with a colour
+    Fill {
+        #[structopt(flatten)]
+        colour: Colour,
+    },
     /// Set button images
     SetImage {
         /// Index of button to be set
@@ -67,7 +114,51 @@ pub enum Commands {
 
         #[structopt(flatten)]
         opts: ImageOptions,
-    }
+    },
+    /// Set button text
+    SetText {
+        /// Index of button to be set
+        key: u8,
+
+        /// Text to be displayed, lines are split on '\n'
+        text: String,
+
+        #[structopt(long)]
+        /// TrueType / OpenType font file, falling back to a built-in font where available
+        font: Option<String>,
+
+        #[structopt(long = "fg", default_value = "FFFFFF")]
+        /// Foreground (text) colour in hex
+        foreground: Colour,
+
+        #[structopt(long = "bg", default_value = "000000")]
+        /// Background colour in hex
+        background: Colour,
+
+        #[structopt(long, default_value = "15.0")]
+        /// Text scale (height in pixels)
+        scale: f32,
+
+        #[structopt(long, default_value = "1.1")]
+        /// Line height as a multiple of text scale
+        line_height: f32,
+
+        #[structopt(long, default_value = "0.0")]
+        /// Additional spacing in pixels between paragraphs (separated by blank lines)
+        paragraph_spacing: f32,
+
+        #[structopt(long, default_value = "0")]
+        /// Horizontal position in pixels
+        x: i32,
+
+        #[structopt(long, default_value = "0")]
+        /// Vertical position in pixels
+        y: i32,
+
+        #[structopt(long)]
+        /// Center text on the key, with x and y as offsets from center
+        center: bool,
+    },
 }
 
 fn main() {
@@ -81,7 +172,14 @@ fn main() {
     TermLogger::init(opts.level, config.build(), TerminalMode::Mixed, ColorChoice::Auto).unwrap();
 
     // Connect to device
-    let mut deck = match StreamDeck::connect(opts.filter.vid, opts.filter.pid, opts.filter.serial) {
+    let res = match &opts.filter.path {
+        Some(p) => match CString::new(p.as_str()) {
+            Ok(p) => StreamDeck::connect_by_path(&p),
+            Err(e) => { error!("Invalid device path: {:?}", e); return }
+        },
+        None => StreamDeck::connect(opts.filter.vid, opts.filter.pid, opts.filter.serial),
+    };
+    let mut deck = match res {
         Ok(d) => d,
         Err(e) => {
             error!("Error connecting to streamdeck: {:?}", e);
@@ -89,17 +187,43 @@ fn main() {
         }
     };
 
-    let serial = deck.serial().unwrap();
-    info!("Connected to device (vid: {:04x} pid: {:04x} serial: {})", 
-            opts.filter.vid, opts.filter.pid, serial);
+    // Clones may not report a serial, so this is optional
+    let serial = deck.serial().unwrap_or_else(|_| "unknown".to_string());
+    let path = deck.path().map(|p| p.to_string_lossy().into_owned());
+    info!("Connected to device: {:?} (serial: {} path: {})",
+            deck.kind(), serial, path.as_deref().unwrap_or("unknown"));
 
     // Run the command
-    if let Err(e) = do_command(&mut deck, opts.cmd) {
+    if let Err(e) = do_command(&mut deck, opts.cmd, opts.json) {
         error!("Command error: {:?}", e);
     }
 }
 
-fn do_command(deck: &mut StreamDeck, cmd: Commands) -> Result<(), Error> {
+/// Read timeout used in continuous modes where none is specified,
+/// so Ctrl-C is handled without waiting for input
+const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Install a Ctrl-C handler, returning a flag cleared when this is received
+fn exit_flag() -> Arc<AtomicBool> {
+    let running = Arc::new(AtomicBool::new(true));
+
+    let r = running.clone();
+    if let Err(e) = ctrlc::set_handler(move || r.store(false, Ordering::SeqCst)) {
+        warn!("Failed to set Ctrl-C handler: {:?}", e);
+    }
+
+    running
+}
+
+fn do_command(deck: &mut StreamDeck, cmd: Commands, json: bool) -> Result<(), Error> {
+    // Output an input event, as JSON or to the log
+    let output_event = |e: &InputEvent| {
+        match json {
+            true => println!("{}", serde_json::to_string(e).unwrap()),
+            false => info!("input: {}", e),
+        }
+    };
+
     match cmd {
         Commands::Reset => {
             deck.reset()?;
@@ -109,20 +233,70 @@ fn do_command(deck: &mut StreamDeck, cmd: Commands) -> Result<(), Error> {
             info!("Firmware version: {}", version);
         }
         Commands::SetBrightness{brightness} => {
-            deck.set_brightness(brightness)?;
+            deck.try_set_brightness(brightness)?;
+        },
+        Commands::Fade{target, duration} => {
+            info!("Fading brightness to {} over {}", target, duration);
+            deck.fade_brightness(target, *duration)?;
         },
-        Commands::GetButtons{timeout, continuous} => {
-            loop {
+        Commands::GetButtons{timeout, continuous, interval} => {
+            if !continuous {
                 let buttons = deck.read_buttons(timeout.map(|t| *t ))?;
                 info!("buttons: {:?}", buttons);
+                return Ok(())
+            }
+
+            let running = exit_flag();
+            let timeout = timeout.map(|t| *t ).unwrap_or(POLL_TIMEOUT);
+
+            while running.load(Ordering::SeqCst) {
+                match deck.read_buttons(Some(timeout)) {
+                    Ok(buttons) => info!("buttons: {:?}", buttons),
+                    Err(Error::NoData) => (),
+                    Err(e) => return Err(e),
+                }
+
+                if let Some(i) = interval {
+                    std::thread::sleep(*i);
+                }
+            }
+        },
+        Commands::GetInput{timeout, continuous, interval} => {
+            let mut manager = InputManager::new(deck);
+
+            if !continuous {
+                let events = manager.handle_input(timeout.map(|t| *t ))?;
+                for e in events {
+                    output_event(&e);
+                }
+                return Ok(())
+            }
+
+            let running = exit_flag();
+            let timeout = timeout.map(|t| *t ).unwrap_or(POLL_TIMEOUT);
+
+            while running.load(Ordering::SeqCst) {
+                for e in manager.handle_input(Some(timeout))? {
+                    output_event(&e);
+                }
 
-                if !continuous {
-                    break
+                if let Some(i) = interval {
+                    std::thread::sleep(*i);
                 }
             }
         },
         Commands::Probe => {
-            let results = StreamDeck::probe()?;
+            let results = StreamDeck::probe_devices(ELGATO_VID)?;
+            if json {
+                for res in results {
+                    let v = match res {
+                        Ok(device) => serde_json::to_value(&device).unwrap(),
+                        Err(e) => serde_json::json!({ "error": e.to_string() }),
+                    };
+                    println!("{}", v);
+                }
+                return Ok(());
+            }
             if results.is_empty() {
                 info!("No devices found");
                 return Ok(());
@@ -130,8 +304,9 @@ fn do_command(deck: &mut StreamDeck, cmd: Commands) -> Result<(), Error> {
             info!("Found {} devices", results.len());
             for res in results {
                 match res {
-                    Ok((device, pid)) => info!("Streamdeck: {:?} (pid: {:#x})", device, pid),
-                    Err(_) => warn!("Found Elgato device with unsupported PID"),
+                    Ok(d) => info!("Streamdeck: {:?} (pid: {:#x} serial: {} path: {})", d.kind, d.pid, d.serial.as_deref().unwrap_or("unknown"), d.path),
+                    Err(Error::UnrecognisedPID(pid)) => warn!("Found Elgato device with unsupported PID: {:#06x}, please report this", pid),
+                    Err(e) => warn!("Probe error: {:?}", e),
                 }
             }
         }
@@ -139,9 +314,55 @@ fn do_command(deck: &mut StreamDeck, cmd: Commands) -> Result<(), Error> {
             info!("Setting key {} colour to: ({:?})", key, colour);
             deck.set_button_rgb(key, &colour)?;
         },
+        Commands::Apply{file} => {
+            info!("Applying layout: {}", file);
+
+            let data = std::fs::read_to_string(&file)?;
+            let layout: Layout = match file.ends_with(".toml") {
+                true => toml::from_str(&data)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+                false => serde_json::from_str(&data)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            };
+
+            layout.apply(deck)?;
+        },
+        Commands::Clear => {
+            info!("Clearing all keys");
+            deck.clear_all()?;
+        },
+        Commands::Fill{colour} => {
+            info!("Setting all keys colour to: ({:?})", colour);
+            deck.fill_all(&colour)?;
+        },
         Commands::SetImage{key, file, opts} => {
             info!("Setting key {} to image: {}", key, file);
             deck.set_button_file(key, &file, &opts)?;
+        },
+        Commands::SetText{key, text, font, foreground, background, scale, line_height, paragraph_spacing, x, y, center} => {
+            info!("Setting key {} to text: {}", key, text);
+
+            // No font is currently embedded, so the font path is required for now
+            let font = match font {
+                Some(f) => f,
+                None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                    "no built-in font available, pass --font").into()),
+            };
+
+            let data = std::fs::read(&font)?;
+            let font = FontRef::try_from_slice(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+            let opts = TextOptions::new(foreground, background, PxScale::from(scale), line_height)
+                .with_paragraph_spacing(paragraph_spacing);
+            let text = text.replace("\\n", "\n");
+
+            let pos = match center {
+                true => TextPosition::Anchored{ anchor: Anchor::Center, offset: (x, y) },
+                false => TextPosition::Absolute{x, y},
+            };
+
+            deck.set_button_text(key, &font, &pos, &text, &opts)?;
         }
     }
 
@@ -0,0 +1,170 @@
+use crate::{Colour, DeckSnapshot, DeviceImage, Error, InputEvent, Kind, StreamDeck};
+
+/// Callback for key presses on a [Page]
+///
+/// Returning `Some(index)` switches to the page at the provided index.
+pub type KeyCallback = Box<dyn FnMut(&mut StreamDeck) -> Result<Option<usize>, Error> + Send>;
+
+/// A single page of key images and press callbacks
+pub struct Page {
+    snapshot: DeckSnapshot,
+    callbacks: Vec<Option<KeyCallback>>,
+}
+
+impl Page {
+    /// Create an empty page for the provided device kind
+    pub fn new(kind: Kind) -> Self {
+        Self::from_snapshot(kind, DeckSnapshot::new(kind))
+    }
+
+    /// Create a page from a snapshot of key images (see [StreamDeck::snapshot])
+    pub fn from_snapshot(kind: Kind, snapshot: DeckSnapshot) -> Self {
+        let mut callbacks = vec![];
+        callbacks.resize_with(kind.keys() as usize, || None);
+
+        Self {
+            snapshot,
+            callbacks,
+        }
+    }
+
+    /// Set the image for a key
+    pub fn with_image(mut self, key: u8, image: DeviceImage) -> Result<Self, Error> {
+        self.snapshot.set_image(key, image)?;
+        Ok(self)
+    }
+
+    /// Set the callback for presses of a key
+    pub fn on_press(
+        mut self,
+        key: u8,
+        f: impl FnMut(&mut StreamDeck) -> Result<Option<usize>, Error> + Send + 'static,
+    ) -> Result<Self, Error> {
+        match self.callbacks.get_mut(key as usize) {
+            Some(c) => *c = Some(Box::new(f)),
+            None => return Err(Error::InvalidKeyIndex),
+        }
+        Ok(self)
+    }
+}
+
+/// Page manager for multi-page layouts
+///
+/// Each [Page] holds the images for every key and callbacks for key presses,
+/// input events are dispatched to the current page with `handle_event`.
+#[derive(Default)]
+pub struct Pages {
+    pages: Vec<Page>,
+    current: Option<usize>,
+}
+
+impl Pages {
+    /// Create an empty page manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a page, returning the page index
+    pub fn add(&mut self, page: Page) -> usize {
+        self.pages.push(page);
+        self.pages.len() - 1
+    }
+
+    /// Fetch the index of the page currently shown, if any
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Number of pages
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Whether there are no pages
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Write the page at the provided index to the device
+    ///
+    /// Keys without an image on the page are cleared.
+    /// Returns `Error::NoData` if there is no page at the index.
+    pub fn show(&mut self, deck: &mut StreamDeck, index: usize) -> Result<(), Error> {
+        let page = self.pages.get(index).ok_or(Error::NoData)?;
+
+        let blank = deck.prepare_colour(&Colour { r: 0, g: 0, b: 0 })?;
+        for key in 0..deck.kind().keys() {
+            let image = page.snapshot.image(key).unwrap_or(&blank);
+            deck.write_button_image(key, image)?;
+        }
+
+        self.current = Some(index);
+
+        Ok(())
+    }
+
+    /// Dispatch an input event to the current page
+    ///
+    /// Button presses run the callback for the key (if set), switching
+    /// pages where a page index is returned.
+    pub fn handle_event(&mut self, deck: &mut StreamDeck, event: &InputEvent) -> Result<(), Error> {
+        let key = match event {
+            InputEvent::ButtonPress { index, .. } => *index as usize,
+            _ => return Ok(()),
+        };
+
+        let page = match self.current.and_then(|i| self.pages.get_mut(i)) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let next = match page.callbacks.get_mut(key) {
+            Some(Some(f)) => f(deck)?,
+            _ => None,
+        };
+
+        if let Some(next) = next {
+            self.show(deck, next)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::mock_deck;
+
+    #[test]
+    fn switch_pages() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+
+        let red = deck.prepare_colour(&Colour { r: 255, g: 0, b: 0 }).unwrap();
+
+        let mut pages = Pages::new();
+        let home = Page::new(Kind::Mini).on_press(0, |_| Ok(Some(1))).unwrap();
+        pages.add(home);
+        let other = Page::new(Kind::Mini).with_image(3, red).unwrap();
+        pages.add(other);
+
+        pages.show(&mut deck, 0).unwrap();
+        assert_eq!(pages.current(), Some(0));
+
+        let press = |index| InputEvent::ButtonPress {
+            index,
+            row: index / 3,
+            col: index % 3,
+        };
+
+        pages.handle_event(&mut deck, &press(1)).unwrap();
+        assert_eq!(pages.current(), Some(0));
+
+        mock.clear();
+        pages.handle_event(&mut deck, &press(0)).unwrap();
+        assert_eq!(pages.current(), Some(1));
+        assert!(!mock.writes().is_empty());
+
+        assert!(pages.show(&mut deck, 2).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+use crate::images::{apply_transform, invert_transform};
+use crate::info::{ColourOrder, ImageMode};
+use crate::{rgb_to_bgr, Error, Kind, MockDevice, StreamDeck};
+
+/// Preview device for visualising layouts without hardware, see [StreamDeck::preview]
+/// and [StreamDeck::virtual_device]
+///
+/// This dereferences to a [StreamDeck] using a mock transport with state tracking
+/// enabled, so the images written to each key can be decoded, rendered and saved.
+pub struct PreviewDeck {
+    deck: StreamDeck,
+    mock: MockDevice,
+}
+
+impl PreviewDeck {
+    pub(crate) fn new(kind: Kind, mock: MockDevice) -> Self {
+        let mut deck = StreamDeck::with_transport(kind, Box::new(mock.clone()));
+        deck.track_state(true);
+
+        Self { deck, mock }
+    }
+
+    /// Fetch the underlying mock transport, for inspecting written reports
+    /// (where recorded) or queueing input
+    pub fn mock(&self) -> &MockDevice {
+        &self.mock
+    }
+
+    /// Decode the image last written to a key, as it would appear to the user
+    ///
+    /// Returns `None` for keys that have not been written since creation or reset
+    pub fn key_image(&self, key: u8) -> Result<Option<RgbImage>, Error> {
+        let snapshot = self.deck.snapshot();
+        let image = match snapshot.image(key) {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+
+        let kind = self.deck.kind();
+        let (w, h) = kind.image_size();
+        let mut image = match kind.image_mode() {
+            ImageMode::Bmp => RgbImage::from_raw(w as u32, h as u32, image.data.clone())
+                .ok_or(Error::InvalidImageSize)?,
+            ImageMode::Jpeg => image::load_from_memory_with_format(&image.data, ImageFormat::Jpeg)
+                .map_err(Error::Image)?
+                .to_rgb8(),
+        };
+        if kind.image_colour_order() == ColourOrder::Bgr {
+            rgb_to_bgr(&mut image);
+        }
+
+        // Undo the device orientation to recover the image as written
+        let (rotation, mirror) = invert_transform(self.deck.image_transform());
+        let image = apply_transform(DynamicImage::ImageRgb8(image), rotation, mirror);
+
+        Ok(Some(image.to_rgb8()))
+    }
+
+    /// Render all keys to a single image using the device key layout,
+    /// see [Kind::key_rect]. Keys that have not been written are left black.
+    pub fn render(&self) -> Result<RgbImage, Error> {
+        let kind = self.deck.kind();
+        let (w, h) = kind.canvas_size();
+        let mut canvas = RgbImage::from_pixel(w as u32, h as u32, Rgb([0, 0, 0]));
+
+        for key in 0..kind.keys() {
+            let (x, y, _, _) = kind.key_rect(key).ok_or(Error::InvalidKeyIndex)?;
+            if let Some(image) = self.key_image(key)? {
+                image::imageops::replace(&mut canvas, &image, x as i64, y as i64);
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    /// Save the rendered device as `deck.png` and each written key as `key-NN.png`
+    /// in the provided directory, which must exist
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+
+        self.render()?.save(dir.join("deck.png"))?;
+
+        for key in 0..self.deck.kind().keys() {
+            if let Some(image) = self.key_image(key)? {
+                image.save(dir.join(format!("key-{:02}.png", key)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consume the preview, returning the underlying device
+    pub fn into_inner(self) -> StreamDeck {
+        self.deck
+    }
+}
+
+impl Deref for PreviewDeck {
+    type Target = StreamDeck;
+
+    fn deref(&self) -> &StreamDeck {
+        &self.deck
+    }
+}
+
+impl DerefMut for PreviewDeck {
+    fn deref_mut(&mut self) -> &mut StreamDeck {
+        &mut self.deck
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Colour;
+
+    #[test]
+    fn preview_keys() {
+        for &kind in &[Kind::Mini, Kind::Original, Kind::Xl] {
+            let mut deck = StreamDeck::preview(kind);
+            let colour = Colour { r: 255, g: 0, b: 0 };
+            deck.set_button_rgb(1, &colour).unwrap();
+
+            assert!(deck.key_image(0).unwrap().is_none());
+            let image = deck.key_image(1).unwrap().unwrap();
+            let p = image.get_pixel(image.width() / 2, image.height() / 2);
+            assert!(p[0] > 200 && p[1] < 50 && p[2] < 50, "{:?} {:?}", kind, p);
+
+            let canvas = deck.render().unwrap();
+            let (w, h) = kind.canvas_size();
+            assert_eq!(canvas.dimensions(), (w as u32, h as u32));
+        }
+    }
+
+    #[test]
+    fn virtual_devices() {
+        let mut deck = StreamDeck::virtual_device(Kind::Plus);
+        let colour = Colour { r: 0, g: 0, b: 255 };
+        deck.set_button_rgb(7, &colour).unwrap();
+
+        // Reports are discarded, with key images retained for rendering
+        assert!(deck.mock().writes().is_empty());
+        assert!(deck.key_image(7).unwrap().is_some());
+
+        // Input may be injected
+        let mut report = vec![0x01, 0x00, 0x08, 0x00];
+        report.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        deck.mock().push_input(&report);
+        assert_eq!(deck.read_buttons(None).unwrap()[7], 1);
+    }
+
+    #[test]
+    fn preview_orientation() {
+        // Distinct left and right halves should survive the device transform
+        let mut deck = StreamDeck::preview(Kind::Original);
+        let (w, h) = deck.image_size();
+        let image = RgbImage::from_fn(w as u32, h as u32, |x, _| match x < w as u32 / 2 {
+            true => Rgb([255, 255, 255]),
+            false => Rgb([0, 0, 0]),
+        });
+        deck.set_button_image(0, DynamicImage::ImageRgb8(image))
+            .unwrap();
+
+        let image = deck.key_image(0).unwrap().unwrap();
+        assert_eq!(image.get_pixel(0, 0)[0], 255);
+        assert_eq!(image.get_pixel(w as u32 - 1, 0)[0], 0);
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::input::InputState;
+use crate::{Error, InputEvent, SharedStreamDeck};
+
+/// Interval for polling device input, the device is unlocked between polls
+pub const SCREENSAVER_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Action to take when the device becomes idle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleAction {
+    /// Dim the display to the provided brightness (in percent)
+    Dim(u8),
+    /// Blank the display, see `StreamDeck::sleep`
+    Sleep,
+}
+
+/// Screensaver, dims or sleeps the device display after a period without input
+///
+/// This runs a thread which reads input from the shared device, taking the
+/// configured [IdleAction] once no input has been received for the idle period,
+/// and restoring the last brightness set via `StreamDeck::set_brightness` on the
+/// next input. As this consumes device input, decoded events are forwarded via
+/// [Screensaver::events]. The thread is stopped when the screensaver is dropped,
+/// or on device errors (which are logged) other than undecodable input reports.
+pub struct Screensaver {
+    events: Receiver<InputEvent>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Screensaver {
+    /// Start a screensaver for the provided device
+    pub fn start(deck: SharedStreamDeck, idle: Duration, action: IdleAction) -> Self {
+        let (tx, rx) = channel();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let r = running.clone();
+        let handle = std::thread::spawn(move || {
+            let mut state = InputState::new(deck.lock().kind());
+            let mut last_input = Instant::now();
+            let mut idle_active = false;
+
+            while r.load(Ordering::SeqCst) {
+                let res = deck.with(|d| -> Result<(), Error> {
+                    let events = match state.poll(d, Some(Duration::ZERO)) {
+                        Ok(e) => e.unwrap_or_default(),
+                        Err(Error::UnsupportedInput) | Err(Error::ShortReport(_)) => {
+                            debug!("Ignoring undecodable input report");
+                            vec![]
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    let input = !events.is_empty();
+
+                    // Forward events before applying display actions, so these are not
+                    // lost where an action fails (the receiver may be dropped where events
+                    // are not required)
+                    for e in events {
+                        let _ = tx.send(e);
+                    }
+
+                    if input {
+                        last_input = Instant::now();
+
+                        if idle_active {
+                            debug!("Input received, restoring display");
+                            d.wake()?;
+                            idle_active = false;
+                        }
+                    } else if !idle_active && last_input.elapsed() >= idle {
+                        debug!("No input for {:?}, applying {:?}", idle, action);
+                        match action {
+                            IdleAction::Dim(b) => d.write_brightness(b.min(100))?,
+                            IdleAction::Sleep => d.sleep()?,
+                        }
+                        idle_active = true;
+                    }
+
+                    Ok(())
+                });
+
+                if let Err(e) = res {
+                    error!("Screensaver stopped: {:?}", e);
+                    break;
+                }
+
+                std::thread::sleep(SCREENSAVER_POLL_INTERVAL);
+            }
+        });
+
+        Self {
+            events: rx,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Fetch the receiver for input events read by the screensaver
+    pub fn events(&self) -> &Receiver<InputEvent> {
+        &self.events
+    }
+}
+
+impl Drop for Screensaver {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::mock_deck;
+    use crate::Kind;
+
+    /// Wait for a condition to be met, polling up to a generous deadline
+    fn wait_for(f: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if f() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn dim_and_restore() {
+        let (mut deck, mock) = mock_deck(Kind::Mini);
+        deck.set_brightness(80).unwrap();
+
+        // Brightness written by the last feature report
+        let brightness = || mock.feature_reports().last().map(|r| r[5]);
+
+        let deck = SharedStreamDeck::new(deck);
+        let saver = Screensaver::start(deck, Duration::from_millis(200), IdleAction::Dim(10));
+
+        assert!(wait_for(|| brightness() == Some(10)));
+
+        // Input is forwarded and restores the last brightness set
+        mock.push_input(&[0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let event = saver.events().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            event,
+            InputEvent::ButtonPress {
+                index: 0,
+                row: 0,
+                col: 0
+            }
+        );
+        assert!(wait_for(|| brightness() == Some(80)));
+    }
+}
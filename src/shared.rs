@@ -0,0 +1,41 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::StreamDeck;
+
+/// Thread-safe shared handle to a [StreamDeck]
+///
+/// Clones refer to the same device, and each operation locks the device
+/// so it may be used from multiple threads (ie. by a [crate::Screensaver]
+/// while keys are updated elsewhere).
+#[derive(Clone)]
+pub struct SharedStreamDeck {
+    inner: Arc<Mutex<StreamDeck>>,
+}
+
+impl SharedStreamDeck {
+    /// Create a shared handle, taking ownership of the device
+    pub fn new(deck: StreamDeck) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(deck)),
+        }
+    }
+
+    /// Lock the device for exclusive access
+    ///
+    /// Device state is not invalidated by a panic while locked, so a poisoned
+    /// lock is recovered rather than propagating the panic.
+    pub fn lock(&self) -> MutexGuard<'_, StreamDeck> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run the provided closure with exclusive access to the device
+    pub fn with<R>(&self, f: impl FnOnce(&mut StreamDeck) -> R) -> R {
+        f(&mut self.lock())
+    }
+}
+
+impl From<StreamDeck> for SharedStreamDeck {
+    fn from(deck: StreamDeck) -> Self {
+        Self::new(deck)
+    }
+}
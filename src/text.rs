@@ -0,0 +1,89 @@
+use ab_glyph::{point, Font, FontRef, GlyphId, OutlinedGlyph, PxScale, Rect, ScaleFont};
+use image::{Rgb, RgbImage};
+
+/// Select the index of the first font containing a glyph for the provided character,
+/// using the first font where no font contains the glyph
+fn select_font(fonts: &[FontRef], c: char) -> usize {
+    fonts
+        .iter()
+        .position(|f| f.glyph_id(c) != GlyphId(0))
+        .unwrap_or(0)
+}
+
+/// Lay out a line of text with per-glyph font fallback, calling `f` for each glyph
+///
+/// Glyphs are positioned on the baseline of the first font. Returns the width
+/// and height of the laid out text.
+fn layout_glyphs(
+    scale: PxScale,
+    fonts: &[FontRef],
+    text: &str,
+    mut f: impl FnMut(OutlinedGlyph, Rect),
+) -> (u32, u32) {
+    if fonts.is_empty() {
+        return (0, 0);
+    }
+
+    let ascent = fonts[0].as_scaled(scale).ascent();
+    let (mut w, mut h) = (0f32, 0f32);
+    let mut last: Option<(usize, GlyphId)> = None;
+
+    for c in text.chars() {
+        let index = select_font(fonts, c);
+        let font = &fonts[index];
+        let scaled = font.as_scaled(scale);
+
+        let id = scaled.glyph_id(c);
+
+        // Kerning only applies between glyphs from the same font
+        if let Some((i, prev)) = last {
+            if i == index {
+                w += scaled.kern(prev, id);
+            }
+        }
+        last = Some((index, id));
+
+        let glyph = id.with_scale_and_position(scale, point(w, ascent));
+        w += scaled.h_advance(id);
+
+        if let Some(g) = font.outline_glyph(glyph) {
+            let bb = g.px_bounds();
+            h = h.max(bb.height());
+            f(g, bb);
+        }
+    }
+
+    (w as u32, h as u32)
+}
+
+/// Measure a line of text rendered with per-glyph font fallback
+pub(crate) fn text_size(scale: PxScale, fonts: &[FontRef], text: &str) -> (u32, u32) {
+    layout_glyphs(scale, fonts, text, |_, _| {})
+}
+
+/// Draw a line of text with per-glyph font fallback
+pub(crate) fn draw_text(
+    image: &mut RgbImage,
+    colour: Rgb<u8>,
+    x: i32,
+    y: i32,
+    scale: PxScale,
+    fonts: &[FontRef],
+    text: &str,
+) {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+
+    layout_glyphs(scale, fonts, text, |g, bb| {
+        g.draw(|gx, gy, gv| {
+            let px = gx as i32 + x + bb.min.x.round() as i32;
+            let py = gy as i32 + y + bb.min.y.round() as i32;
+
+            if (0..width).contains(&px) && (0..height).contains(&py) {
+                let p = image.get_pixel_mut(px as u32, py as u32);
+                for (c, v) in p.0.iter_mut().zip(colour.0.iter()) {
+                    *c = (*c as f32 * (1.0 - gv) + *v as f32 * gv).round() as u8;
+                }
+            }
+        })
+    });
+}
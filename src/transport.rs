@@ -0,0 +1,201 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use hidapi::{HidDevice, HidError};
+
+/// Low-level device transport
+///
+/// This abstracts the HID operations used by [crate::StreamDeck], allowing the
+/// protocol to be exercised without hardware (see [MockDevice]). Errors use
+/// [HidError] so that transports report failures in the same manner as hidapi.
+pub trait Transport: Send {
+    /// Write an output report, returning the number of bytes written
+    fn write(&self, data: &[u8]) -> Result<usize, HidError>;
+
+    /// Read an input report (blocking where enabled), returning the number of bytes read
+    fn read(&self, buf: &mut [u8]) -> Result<usize, HidError>;
+
+    /// Read an input report with a timeout in milliseconds, returning the number of
+    /// bytes read or zero if no report was available
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, HidError>;
+
+    /// Send a feature report, the first byte of `data` is the report ID
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), HidError>;
+
+    /// Fetch a feature report, the first byte of `buf` is the report ID
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, HidError>;
+
+    /// Set blocking mode for reads
+    fn set_blocking_mode(&self, blocking: bool) -> Result<(), HidError>;
+
+    fn get_manufacturer_string(&self) -> Result<Option<String>, HidError>;
+
+    fn get_product_string(&self) -> Result<Option<String>, HidError>;
+
+    fn get_serial_number_string(&self) -> Result<Option<String>, HidError>;
+}
+
+impl Transport for HidDevice {
+    fn write(&self, data: &[u8]) -> Result<usize, HidError> {
+        HidDevice::write(self, data)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, HidError> {
+        HidDevice::read(self, buf)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, HidError> {
+        HidDevice::read_timeout(self, buf, timeout)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), HidError> {
+        HidDevice::send_feature_report(self, data)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, HidError> {
+        HidDevice::get_feature_report(self, buf)
+    }
+
+    fn set_blocking_mode(&self, blocking: bool) -> Result<(), HidError> {
+        HidDevice::set_blocking_mode(self, blocking)
+    }
+
+    fn get_manufacturer_string(&self) -> Result<Option<String>, HidError> {
+        HidDevice::get_manufacturer_string(self)
+    }
+
+    fn get_product_string(&self) -> Result<Option<String>, HidError> {
+        HidDevice::get_product_string(self)
+    }
+
+    fn get_serial_number_string(&self) -> Result<Option<String>, HidError> {
+        HidDevice::get_serial_number_string(self)
+    }
+}
+
+/// Mock device transport, recording output and replaying queued input
+///
+/// Clones share the same state, so a clone may be retained to inspect reports
+/// written once the device has been passed to [crate::StreamDeck::with_transport].
+#[derive(Clone, Default)]
+pub struct MockDevice {
+    inner: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    writes: Vec<Vec<u8>>,
+    feature_reports: Vec<Vec<u8>>,
+    feature_responses: HashMap<u8, Vec<u8>>,
+    input: VecDeque<Vec<u8>>,
+    discard: bool,
+}
+
+impl MockDevice {
+    /// Create a new mock device
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a mock device which discards written output and feature reports,
+    /// for long-running use where these are not inspected
+    pub fn discarding() -> Self {
+        let d = Self::default();
+        d.state().discard = true;
+        d
+    }
+
+    /// Fetch output reports written to the device
+    pub fn writes(&self) -> Vec<Vec<u8>> {
+        self.state().writes.clone()
+    }
+
+    /// Fetch feature reports sent to the device
+    pub fn feature_reports(&self) -> Vec<Vec<u8>> {
+        self.state().feature_reports.clone()
+    }
+
+    /// Clear recorded output and feature reports
+    pub fn clear(&self) {
+        let mut s = self.state();
+        s.writes.clear();
+        s.feature_reports.clear();
+    }
+
+    /// Queue an input report to be returned by the next read
+    pub fn push_input(&self, report: &[u8]) {
+        self.state().input.push_back(report.to_vec());
+    }
+
+    /// Set the response to feature report requests (keyed by the first byte of `report`)
+    pub fn set_feature_response(&self, report: &[u8]) {
+        if let Some(id) = report.first() {
+            self.state().feature_responses.insert(*id, report.to_vec());
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for MockDevice {
+    fn write(&self, data: &[u8]) -> Result<usize, HidError> {
+        let mut s = self.state();
+        if !s.discard {
+            s.writes.push(data.to_vec());
+        }
+        Ok(data.len())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, HidError> {
+        let report = match self.state().input.pop_front() {
+            Some(r) => r,
+            None => return Ok(0),
+        };
+
+        let n = report.len().min(buf.len());
+        buf[..n].copy_from_slice(&report[..n]);
+        Ok(n)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], _timeout: i32) -> Result<usize, HidError> {
+        self.read(buf)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), HidError> {
+        let mut s = self.state();
+        if !s.discard {
+            s.feature_reports.push(data.to_vec());
+        }
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, HidError> {
+        let s = self.state();
+        let report = match buf.first().and_then(|id| s.feature_responses.get(id)) {
+            Some(r) => r,
+            None => return Ok(0),
+        };
+
+        let n = report.len().min(buf.len());
+        buf[..n].copy_from_slice(&report[..n]);
+        Ok(n)
+    }
+
+    fn set_blocking_mode(&self, _blocking: bool) -> Result<(), HidError> {
+        Ok(())
+    }
+
+    fn get_manufacturer_string(&self) -> Result<Option<String>, HidError> {
+        Ok(Some("Elgato".to_string()))
+    }
+
+    fn get_product_string(&self) -> Result<Option<String>, HidError> {
+        Ok(Some("Mock Stream Deck".to_string()))
+    }
+
+    fn get_serial_number_string(&self) -> Result<Option<String>, HidError> {
+        Ok(Some("MOCK".to_string()))
+    }
+}